@@ -1,5 +1,5 @@
 import networkx as nx
-from grpphati_rs import GrPPH_rs
+from grpphati_rs import GrPPH_rs, GrPPH_native_rs
 
 
 def do_job():
@@ -9,8 +9,11 @@ def do_job():
         G.add_edges_from([((i, j), ((i + 1) % N, j)) for i in range(N)])
     G = nx.convert_node_labels_to_integers(G)
     res = GrPPH_rs(G)
-    return res
+    # Each of the 100 components is computed separately, in parallel
+    native_res = GrPPH_native_rs(G)
+    return res, native_res
 
 
-res = do_job()
+res, native_res = do_job()
 print(res.barcode)
+print(native_res.barcode)
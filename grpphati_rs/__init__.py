@@ -1,4 +1,11 @@
 from .rrph import RustRegularPathHomology
+from .nrph import RustNonRegularPathHomology
 from .dflag import RustDirectedFlagComplexHomology
 from .sparsifier import RustGeneratorSparsifier, RustPreferredSparsifier
-from .pipelines import GrPPH_rs, GrPPH_par_wedge_rs
+from .pipelines import (
+    GrPPH_rs,
+    GrPPH_par_wedge_rs,
+    GrPdFlH_rs,
+    GrPPH_native_rs,
+    GrPdFlH_native_rs,
+)
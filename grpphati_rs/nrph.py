@@ -0,0 +1,53 @@
+from grpphati.homologies import Homology
+from grpphati.filtrations import Filtration
+from grpphati_rs.grpphati_rs import (
+    get_nrph_two_cells,
+    get_one_cells as get_rs_one_cells,
+    GrpphatiRsColumn,
+    compute_nrph_map,
+    verify_nrph_chain_map,
+)
+
+
+class RustNonRegularPathHomology(Homology):
+    @classmethod
+    def get_zero_cells(cls, filtration: Filtration):
+        return [
+            GrpphatiRsColumn("Node", [node], time)
+            for node, time in filtration.node_iter()
+        ]
+
+    @classmethod
+    def get_one_cells(cls, filtration: Filtration):
+        edge_map = {}
+        for (i, j), time in filtration.edge_iter():
+            edge_map.setdefault(i, {})[j] = time
+        return get_rs_one_cells(edge_map, dict(filtration.node_iter()))
+
+    @classmethod
+    def get_two_cells(cls, filtration: Filtration):
+        return get_nrph_two_cells(filtration.edge_dict(), dict(filtration.node_iter()))
+
+    @classmethod
+    def compute_map(cls, domain, codomain, domain_node_list, vertex_map=lambda x: x):
+        collected_map = {node: vertex_map(node) for node in domain_node_list}
+        return compute_nrph_map(domain, codomain, collected_map)
+
+    @classmethod
+    def verify_map(cls, domain, codomain, domain_node_list, vertex_map=lambda x: x):
+        collected_map = {node: vertex_map(node) for node in domain_node_list}
+        verify_nrph_chain_map(domain, codomain, collected_map)
+
+    @staticmethod
+    def get_relabelled_inclusion(domain_G, codomain_G, label_attribute="original"):
+        def inclusion(x):
+            target_original = domain_G.nodes[x][label_attribute]
+            for y, data in codomain_G.nodes(data=True):
+                if data[label_attribute] == target_original:
+                    return y
+
+            raise ValueError(
+                f"Node with original label {target_original} does not exist in codomain"
+            )
+
+        return inclusion
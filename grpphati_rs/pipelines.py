@@ -1,5 +1,13 @@
-from grpphati_rs import RustRegularPathHomology, RustPreferredSparsifier
-from grpphati_rs.grpphati_rs import sparsify_and_decompose
+from grpphati_rs import (
+    RustRegularPathHomology,
+    RustDirectedFlagComplexHomology,
+    RustPreferredSparsifier,
+)
+from grpphati_rs.grpphati_rs import (
+    sparsify_and_decompose,
+    grpph_pipeline,
+    grpdflh_pipeline,
+)
 from grpphati.filtrations import ShortestPathFiltration
 from grpphati.optimisations import component_appendage_empty, all_optimisations
 from grpphati.backends import LoPHATBackend, Backend
@@ -11,12 +19,12 @@ from lophat import LoPhatOptions, compute_pairings
 
 
 class GrpphatiRsBackend(Backend):
-    def __init__(self):
-        pass
+    def __init__(self, prime=2):
+        self.prime = prime
 
     def compute_ph(self, cols) -> Result:
         cols.sort(key=lambda col: (col.dimension(), col.get_entrance_time()))
-        diagram = sparsify_and_decompose(cols)
+        diagram = sparsify_and_decompose(cols, prime=self.prime)
         result = Result.empty()
         result.add_paired(diagram.paired, cols, reps=None)
         result.add_unpaired_raw(diagram.unpaired, cols, reps=None)
@@ -38,3 +46,70 @@ GrPPH_par_wedge_rs = make_grounded_pipeline(
     optimisation_strat=all_optimisations,
     truncation_strat=cone_time,
 )
+
+# The appendage and wedge optimisations only hold for path homology,
+# and cone_time does not check that the apex of the cone has edges in one direction only
+GrPdFlH_rs = make_grounded_pipeline(
+    ShortestPathFiltration,
+    RustDirectedFlagComplexHomology,
+    backend=GrpphatiRsBackend(),
+    optimisation_strat=None,
+    truncation_strat=None,
+)
+
+
+def _native_result(cols, diagram):
+    result = Result.empty()
+    result.add_paired(diagram.paired, cols, reps=None)
+    result.add_unpaired_raw(diagram.unpaired, cols, reps=None)
+    return result
+
+
+def _weighted_edges(G, weight):
+    return [(u, v, data.get(weight, 1)) for u, v, data in G.edges(data=True)]
+
+
+def GrPPH_native_rs(
+    G,
+    filtration="shortest_path",
+    weight="weight",
+    wedge=False,
+    prune=False,
+    truncation="none",
+    max_time=None,
+):
+    """Grounded persistent path homology computed entirely in Rust.
+
+    ``filtration`` is one of ``"shortest_path"``, ``"weight"``, ``"hop_count"`` or ``"minimax"``.
+    Missing edge weights default to 1.
+    With ``wedge=True`` the graph is split at its cut vertices and each block is computed in parallel.
+    With ``prune=True`` dangling trees are removed first, like ``component_appendage_empty``.
+    With ``truncation="cone"`` each part is truncated once it contains a cone and all of its own edges,
+    and ``max_time`` truncates every part at a fixed time.
+    """
+    cols, diagram = grpph_pipeline(
+        list(G.nodes),
+        _weighted_edges(G, weight),
+        filtration,
+        wedge,
+        prune,
+        truncation,
+        max_time,
+    )
+    return _native_result(cols, diagram)
+
+
+def GrPdFlH_native_rs(
+    G, filtration="shortest_path", weight="weight", truncation="none", max_time=None
+):
+    """Grounded persistent directed flag complex homology computed entirely in Rust.
+
+    Takes the same arguments as ``GrPPH_native_rs``, except for ``wedge`` and ``prune``,
+    which are not valid for the directed flag complex.
+    Each weakly connected component is still computed in parallel.
+    Cone truncation additionally waits until the apex of the cone has edges in one direction only.
+    """
+    cols, diagram = grpdflh_pipeline(
+        list(G.nodes), _weighted_edges(G, weight), filtration, truncation, max_time
+    )
+    return _native_result(cols, diagram)
@@ -1,6 +1,12 @@
 from grpphati.homologies import Homology
 from grpphati.filtrations import Filtration
-from grpphati_rs.grpphati_rs import get_rph_two_cells, GrpphatiRsColumn, compute_rph_map
+from grpphati_rs.grpphati_rs import (
+    get_rph_two_cells,
+    get_one_cells as get_rs_one_cells,
+    GrpphatiRsColumn,
+    compute_rph_map,
+    verify_rph_chain_map,
+)
 
 
 class RustRegularPathHomology(Homology):
@@ -13,20 +19,25 @@ class RustRegularPathHomology(Homology):
 
     @classmethod
     def get_one_cells(cls, filtration: Filtration):
-        return [
-            GrpphatiRsColumn("Edge", [edge[0], edge[1]], time)
-            for edge, time in filtration.edge_iter()
-        ]
+        edge_map = {}
+        for (i, j), time in filtration.edge_iter():
+            edge_map.setdefault(i, {})[j] = time
+        return get_rs_one_cells(edge_map, dict(filtration.node_iter()))
 
     @classmethod
     def get_two_cells(cls, filtration: Filtration):
-        return get_rph_two_cells(filtration.edge_dict())
+        return get_rph_two_cells(filtration.edge_dict(), dict(filtration.node_iter()))
 
     @classmethod
     def compute_map(cls, domain, codomain, domain_node_list, vertex_map=lambda x: x):
         collected_map = {node: vertex_map(node) for node in domain_node_list}
         return compute_rph_map(domain, codomain, collected_map)
 
+    @classmethod
+    def verify_map(cls, domain, codomain, domain_node_list, vertex_map=lambda x: x):
+        collected_map = {node: vertex_map(node) for node in domain_node_list}
+        verify_rph_chain_map(domain, codomain, collected_map)
+
     @staticmethod
     def get_relabelled_inclusion(domain_G, codomain_G, label_attribute="original"):
         def inclusion(x):
@@ -0,0 +1,229 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Parser, ValueEnum};
+use lophat::{options::LoPhatOptions, utils::PersistenceDiagram};
+
+use grpphati_rs::{
+    coefficients::PrimeField,
+    columns::GrpphatiRsColumn,
+    decompose_columns_using, decompose_with_representatives,
+    filtrations::{build_filtration, FiltrationKind},
+    pipelines::{grounded_cells, HomologyKind},
+    readers::{read_edge_list, read_graphml, ColumnRef, EdgeListOptions, LabelledDigraph},
+    serialization::{diagram_bars, write_bars, DataFormat, DiagramBar},
+    truncations::{Truncation, TruncationStrategy},
+    Algorithm, DecompositionOptions, FiltrationTime,
+};
+
+/// Computes grounded persistent path homology (rph) or grounded persistent directed flag homology (dflag)
+/// of a weighted digraph, writing the barcode to stdout.
+#[derive(Parser, Debug)]
+#[command(name = "grpphati-rs", version)]
+struct Args {
+    /// Graph file, an edge list, CSV or GraphML
+    input: PathBuf,
+    /// Input format, inferred from the file extension by default
+    #[arg(long, value_enum)]
+    format: Option<InputFormat>,
+    /// Treat the first line of an edge list as a header
+    #[arg(long)]
+    header: bool,
+    /// Source column of an edge list, by position or header name
+    #[arg(long, default_value = "0")]
+    source: String,
+    /// Target column of an edge list, by position or header name
+    #[arg(long, default_value = "1")]
+    target: String,
+    /// Weight column of an edge list, or the weight attribute of a GraphML file
+    #[arg(long)]
+    weight: Option<String>,
+    /// Give every edge of an edge list weight 1
+    #[arg(long, conflicts_with = "weight")]
+    unweighted: bool,
+    /// Homology theory, rph or dflag
+    #[arg(long, default_value = "rph")]
+    homology: HomologyKind,
+    /// Filtration, one of shortest_path, weight, hop_count or minimax
+    #[arg(long, default_value = "shortest_path")]
+    filtration: FiltrationKind,
+    /// Truncation strategy, none or cone
+    #[arg(long, default_value = "cone")]
+    truncation: TruncationStrategy,
+    /// Drop all edges entering after this time
+    #[arg(long)]
+    max_time: Option<FiltrationTime>,
+    /// Number of threads, defaults to the number of CPUs
+    #[arg(long)]
+    threads: Option<usize>,
+    /// Include a representative for each bar
+    #[arg(long)]
+    reps: bool,
+    /// Compute representatives with the serial algorithm, so that they are the same on every run
+    #[arg(long, requires = "reps")]
+    deterministic: bool,
+    /// Write the label of each node index to this CSV file
+    #[arg(long)]
+    labels: Option<PathBuf>,
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Csv)]
+    output: OutputFormat,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum InputFormat {
+    EdgeList,
+    Csv,
+    Graphml,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum OutputFormat {
+    Csv,
+    Json,
+}
+
+impl From<OutputFormat> for DataFormat {
+    fn from(format: OutputFormat) -> Self {
+        match format {
+            OutputFormat::Csv => DataFormat::Csv,
+            OutputFormat::Json => DataFormat::Json,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("Error: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .map_err(|e| e.to_string())?;
+    }
+    let LabelledDigraph {
+        graph,
+        labels,
+        skipped_self_loops,
+    } = read_graph(args)?;
+    if skipped_self_loops > 0 {
+        eprintln!("Skipped {skipped_self_loops} self-loops");
+    }
+    eprintln!(
+        "Read {} nodes and {} edges",
+        graph.nodes.len(),
+        graph.edges.len()
+    );
+    let mut edge_map = build_filtration(&graph, args.filtration);
+    let truncation = Truncation {
+        strategy: args.truncation,
+        max_time: args.max_time,
+    };
+    let max_time = truncation.apply(&graph, &mut edge_map, args.homology);
+    if max_time.is_finite() {
+        eprintln!("Truncating at {max_time}");
+    }
+    if let Some(path) = &args.labels {
+        write_labels(path, &labels)?;
+    }
+    // Progress goes to stderr, keeping stdout for the bars
+    let progress = |message: &str| eprintln!("{message}");
+    let cols = grounded_cells(&graph, &edge_map, args.homology, &progress);
+    let lophat = LoPhatOptions {
+        num_threads: args.threads.unwrap_or(0),
+        min_chunk_len: 10000,
+        ..Default::default()
+    };
+    let (diagram, representatives) = if args.reps {
+        let algorithm = if args.deterministic {
+            Algorithm::Serial
+        } else {
+            Algorithm::LockFree
+        };
+        let (diagram, reps) = decompose_with_representatives(&cols, algorithm, lophat, &progress);
+        (diagram, Some(reps))
+    } else {
+        let field = PrimeField::new(2).expect("2 is prime");
+        let options = DecompositionOptions::resolve(2, None, None, lophat, cols.len())?;
+        let diagram = decompose_columns_using(&cols, field, options, &progress);
+        (diagram, None)
+    };
+    let bars = collect_bars(&cols, &diagram, representatives.as_ref())?;
+    write_bars(io::stdout().lock(), &bars, args.output.into())
+}
+
+fn read_graph(args: &Args) -> Result<LabelledDigraph, String> {
+    let format =
+        args.format.unwrap_or_else(
+            || match args.input.extension().and_then(|ext| ext.to_str()) {
+                Some("graphml") => InputFormat::Graphml,
+                Some("csv") => InputFormat::Csv,
+                _ => InputFormat::EdgeList,
+            },
+        );
+    let file = File::open(&args.input).map_err(|e| format!("{}: {e}", args.input.display()))?;
+    let reader = BufReader::new(file);
+    if let InputFormat::Graphml = format {
+        return read_graphml(reader, args.weight.as_deref().unwrap_or("weight"));
+    }
+    let column = |column: &str| match column.parse() {
+        Ok(idx) => ColumnRef::Index(idx),
+        Err(_) => ColumnRef::Name(column.to_string()),
+    };
+    let options = EdgeListOptions {
+        delimiter: match format {
+            InputFormat::Csv => Some(b','),
+            _ => None,
+        },
+        has_header: args.header,
+        source: column(&args.source),
+        target: column(&args.target),
+        weight: match (&args.weight, args.unweighted) {
+            (_, true) => None,
+            (Some(weight), false) => Some(column(weight)),
+            (None, false) => Some(ColumnRef::Index(2)),
+        },
+    };
+    read_edge_list(reader, &options)
+}
+
+// Bars of positive length in dimensions 0 and 1.
+// There are no 3-cells, so bars in dimension 2 are meaningless
+fn collect_bars(
+    cols: &[GrpphatiRsColumn],
+    diagram: &PersistenceDiagram,
+    representatives: Option<&HashMap<usize, Vec<usize>>>,
+) -> Result<Vec<DiagramBar>, String> {
+    let mut bars = diagram_bars(cols, diagram, representatives)?;
+    bars.retain(|bar| bar.dimension <= 1 && bar.death != Some(bar.birth));
+    Ok(bars)
+}
+
+// One `node,label` line per node, so that the node indices in representatives can be read back
+fn write_labels(path: &Path, labels: &[String]) -> Result<(), String> {
+    let error = |e: csv::Error| format!("{}: {e}", path.display());
+    let mut writer = csv::Writer::from_path(path).map_err(error)?;
+    writer.write_record(["node", "label"]).map_err(error)?;
+    for (node, label) in labels.iter().enumerate() {
+        writer
+            .write_record([node.to_string().as_str(), label])
+            .map_err(error)?;
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("{}: {e}", path.display()))
+}
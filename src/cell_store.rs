@@ -0,0 +1,476 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+};
+
+use pyo3::{
+    exceptions::{PyIOError, PyValueError},
+    prelude::*,
+};
+
+use crate::{
+    columns::{ColumnType, GrpphatiRsColumn},
+    file_formats::{invalid_data, BoundaryMatrix},
+    filtrations::FiltrationKind,
+    FiltrationTime, NodeIndex,
+};
+
+// Files are little-endian, with every section 8-byte aligned so that they can be memory-mapped.
+// Both kinds of file start with a 64 byte header:
+//   magic [u8; 8], version u32, reserved u32, homology [u8; 16], filtration [u8; 16],
+//   max_time f64, node_count u64
+// where names are zero-padded and max_time is infinite if the filtration was not truncated.
+// Reserved fields are zero, and are checked along with the length of the file when loading.
+// Cell files continue with the number of cells as a u64, then a 32 byte record per cell:
+//   entrance_time f64 (NaN if absent), kind u32, nodes [u32; 4] (in Display order, zero-padded), reserved u32
+// Matrix files continue with the number of columns and of entries as u64s, then in compressed sparse column form
+//   dimensions [u64; n_cols], offsets [u64; n_cols + 1], rows [u64; n_entries]
+const CELLS_MAGIC: &[u8; 8] = b"GRPPHCEL";
+const MATRIX_MAGIC: &[u8; 8] = b"GRPPHMAT";
+/// Files written by a newer version are rejected.
+pub const STORE_VERSION: u32 = 1;
+const NAME_LEN: usize = 16;
+const HOMOLOGIES: [&str; 3] = ["rph", "nrph", "dflag"];
+
+/// Describes the complex stored in a cell or matrix file.
+#[pyclass(get_all)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoreHeader {
+    pub version: u32,
+    /// One of rph, nrph or dflag.
+    pub homology: String,
+    /// The filtration the cells were built from, if known.
+    pub filtration: Option<String>,
+    /// The time at which the filtration was truncated, if it was.
+    pub max_time: Option<FiltrationTime>,
+    pub node_count: u64,
+}
+
+impl StoreHeader {
+    pub fn new(
+        homology: &str,
+        node_count: u64,
+        filtration: Option<FiltrationKind>,
+        max_time: Option<FiltrationTime>,
+    ) -> Result<Self, String> {
+        if !HOMOLOGIES.contains(&homology) {
+            return Err(format!(
+                "Unknown homology {homology}, expected one of rph, nrph or dflag"
+            ));
+        }
+        if max_time.is_some_and(|time| time.is_nan()) {
+            return Err("max_time must not be NaN".to_string());
+        }
+        Ok(Self {
+            version: STORE_VERSION,
+            homology: homology.to_string(),
+            filtration: filtration.map(|kind| kind.to_string()),
+            max_time: max_time.filter(|time| time.is_finite()),
+            node_count,
+        })
+    }
+}
+
+#[pymethods]
+impl StoreHeader {
+    fn __repr__(&self) -> String {
+        format!(
+            "StoreHeader(version={}, homology='{}', filtration={}, max_time={}, node_count={})",
+            self.version,
+            self.homology,
+            self.filtration
+                .as_ref()
+                .map_or("None".to_string(), |name| format!("'{name}'")),
+            self.max_time
+                .map_or("None".to_string(), |time| time.to_string()),
+            self.node_count
+        )
+    }
+}
+
+/// Saves the cells to `path` in a versioned binary format, recording the homology (rph, nrph or dflag),
+/// the number of nodes and optionally the filtration and truncation time they were built with.
+#[pyfunction]
+#[pyo3(name = "save_cells")]
+#[pyo3(signature = (path, cols, homology, node_count, filtration = None, max_time = None))]
+pub fn save_cells_py(
+    path: &str,
+    cols: Vec<GrpphatiRsColumn>,
+    homology: &str,
+    node_count: u64,
+    filtration: Option<&str>,
+    max_time: Option<FiltrationTime>,
+) -> PyResult<()> {
+    let header = header_from_py(homology, node_count, filtration, max_time)?;
+    let file = File::create(path).map_err(|e| PyIOError::new_err(format!("{path}: {e}")))?;
+    save_cells(BufWriter::new(file), &cols, &header).map_err(io_error_to_py)
+}
+
+/// Loads cells saved by `save_cells`, returning them with the header.
+/// The cells are copied into Python objects; see the layout in `src/cell_store.rs` to memory-map them instead.
+#[pyfunction]
+#[pyo3(name = "load_cells")]
+pub fn load_cells_py(path: &str) -> PyResult<(Vec<GrpphatiRsColumn>, StoreHeader)> {
+    let file = File::open(path).map_err(|e| PyIOError::new_err(format!("{path}: {e}")))?;
+    load_cells(BufReader::new(file)).map_err(io_error_to_py)
+}
+
+/// Saves the `(dimension, boundary)` output of a sparsifier to `path`, with a header as in `save_cells`.
+#[pyfunction]
+#[pyo3(name = "save_sparse_matrix")]
+#[pyo3(signature = (path, matrix, homology, node_count, filtration = None, max_time = None))]
+pub fn save_sparse_matrix_py(
+    path: &str,
+    matrix: BoundaryMatrix,
+    homology: &str,
+    node_count: u64,
+    filtration: Option<&str>,
+    max_time: Option<FiltrationTime>,
+) -> PyResult<()> {
+    let header = header_from_py(homology, node_count, filtration, max_time)?;
+    let file = File::create(path).map_err(|e| PyIOError::new_err(format!("{path}: {e}")))?;
+    save_matrix(BufWriter::new(file), &matrix, &header).map_err(io_error_to_py)
+}
+
+/// Loads a matrix saved by `save_sparse_matrix`, returning it with the header.
+#[pyfunction]
+#[pyo3(name = "load_sparse_matrix")]
+pub fn load_sparse_matrix_py(path: &str) -> PyResult<(BoundaryMatrix, StoreHeader)> {
+    let file = File::open(path).map_err(|e| PyIOError::new_err(format!("{path}: {e}")))?;
+    load_matrix(BufReader::new(file)).map_err(io_error_to_py)
+}
+
+fn header_from_py(
+    homology: &str,
+    node_count: u64,
+    filtration: Option<&str>,
+    max_time: Option<FiltrationTime>,
+) -> PyResult<StoreHeader> {
+    let filtration = filtration
+        .map(str::parse::<FiltrationKind>)
+        .transpose()
+        .map_err(PyValueError::new_err)?;
+    StoreHeader::new(homology, node_count, filtration, max_time).map_err(PyValueError::new_err)
+}
+
+fn io_error_to_py(e: io::Error) -> PyErr {
+    match e.kind() {
+        io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput => {
+            PyValueError::new_err(e.to_string())
+        }
+        _ => PyIOError::new_err(e.to_string()),
+    }
+}
+
+pub fn save_cells(
+    mut writer: impl Write,
+    cols: &[GrpphatiRsColumn],
+    header: &StoreHeader,
+) -> io::Result<()> {
+    write_header(&mut writer, CELLS_MAGIC, header)?;
+    writer.write_all(&(cols.len() as u64).to_le_bytes())?;
+    for col in cols {
+        let (kind, nodes) = encode_cell(&col.col_type);
+        let time = col.entrance_time.unwrap_or(FiltrationTime::NAN);
+        writer.write_all(&time.to_le_bytes())?;
+        writer.write_all(&kind.to_le_bytes())?;
+        for node in nodes {
+            writer.write_all(&node.to_le_bytes())?;
+        }
+        writer.write_all(&0u32.to_le_bytes())?;
+    }
+    writer.flush()
+}
+
+pub fn load_cells(mut reader: impl Read) -> io::Result<(Vec<GrpphatiRsColumn>, StoreHeader)> {
+    let header = read_header(&mut reader, CELLS_MAGIC)?;
+    let n_cells = read_u64(&mut reader)?;
+    let mut cols = Vec::new();
+    for _ in 0..n_cells {
+        let mut record = [0; 32];
+        reader.read_exact(&mut record)?;
+        let time = FiltrationTime::from_le_bytes(record[0..8].try_into().unwrap());
+        let kind = u32::from_le_bytes(record[8..12].try_into().unwrap());
+        let mut nodes = [0; 4];
+        for (idx, node) in nodes.iter_mut().enumerate() {
+            let start = 12 + 4 * idx;
+            *node = NodeIndex::from_le_bytes(record[start..start + 4].try_into().unwrap());
+        }
+        check_reserved(u32::from_le_bytes(record[28..32].try_into().unwrap()))?;
+        let col_type = decode_cell(kind, nodes)?;
+        if encode_cell(&col_type).1 != nodes {
+            return Err(invalid_data(format!("{col_type} has non-zero padding")));
+        }
+        cols.push(GrpphatiRsColumn {
+            col_type,
+            entrance_time: (!time.is_nan()).then_some(time),
+        });
+    }
+    check_end(&mut reader)?;
+    Ok((cols, header))
+}
+
+pub fn save_matrix(
+    mut writer: impl Write,
+    matrix: &[(usize, Vec<usize>)],
+    header: &StoreHeader,
+) -> io::Result<()> {
+    write_header(&mut writer, MATRIX_MAGIC, header)?;
+    let n_entries: usize = matrix.iter().map(|(_, boundary)| boundary.len()).sum();
+    for value in [matrix.len(), n_entries] {
+        writer.write_all(&(value as u64).to_le_bytes())?;
+    }
+    for (dim, _) in matrix {
+        writer.write_all(&(*dim as u64).to_le_bytes())?;
+    }
+    let mut offset = 0;
+    writer.write_all(&0u64.to_le_bytes())?;
+    for (_, boundary) in matrix {
+        offset += boundary.len();
+        writer.write_all(&(offset as u64).to_le_bytes())?;
+    }
+    for (_, boundary) in matrix {
+        for &row in boundary {
+            writer.write_all(&(row as u64).to_le_bytes())?;
+        }
+    }
+    writer.flush()
+}
+
+pub fn load_matrix(mut reader: impl Read) -> io::Result<(BoundaryMatrix, StoreHeader)> {
+    let header = read_header(&mut reader, MATRIX_MAGIC)?;
+    let n_cols = read_usize(&mut reader)?;
+    let n_entries = read_usize(&mut reader)?;
+    let dims = (0..n_cols)
+        .map(|_| read_usize(&mut reader))
+        .collect::<io::Result<Vec<_>>>()?;
+    let offsets = (0..=n_cols)
+        .map(|_| read_usize(&mut reader))
+        .collect::<io::Result<Vec<_>>>()?;
+    let rows = (0..n_entries)
+        .map(|_| read_usize(&mut reader))
+        .collect::<io::Result<Vec<_>>>()?;
+    check_end(&mut reader)?;
+    let valid_offsets = offsets[0] == 0
+        && offsets[n_cols] == n_entries
+        && offsets.windows(2).all(|pair| pair[0] <= pair[1]);
+    if !valid_offsets {
+        return Err(invalid_data("Invalid column offsets".to_string()));
+    }
+    let matrix = dims
+        .into_iter()
+        .zip(offsets.windows(2))
+        .map(|(dim, pair)| (dim, rows[pair[0]..pair[1]].to_vec()))
+        .collect();
+    Ok((matrix, header))
+}
+
+fn write_header(writer: &mut impl Write, magic: &[u8; 8], header: &StoreHeader) -> io::Result<()> {
+    writer.write_all(magic)?;
+    writer.write_all(&STORE_VERSION.to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?;
+    write_name(writer, &header.homology)?;
+    write_name(writer, header.filtration.as_deref().unwrap_or(""))?;
+    let max_time = header.max_time.unwrap_or(FiltrationTime::INFINITY);
+    writer.write_all(&max_time.to_le_bytes())?;
+    writer.write_all(&header.node_count.to_le_bytes())
+}
+
+fn read_header(reader: &mut impl Read, magic: &[u8; 8]) -> io::Result<StoreHeader> {
+    let mut file_magic = [0; 8];
+    reader.read_exact(&mut file_magic)?;
+    if &file_magic != magic {
+        let expected = if magic == CELLS_MAGIC {
+            "cell"
+        } else {
+            "matrix"
+        };
+        return Err(invalid_data(format!("Not a {expected} file")));
+    }
+    let mut words = [0; 8];
+    reader.read_exact(&mut words)?;
+    let version = u32::from_le_bytes(words[0..4].try_into().unwrap());
+    if version > STORE_VERSION {
+        return Err(invalid_data(format!(
+            "File has version {version} but only versions up to {STORE_VERSION} are supported"
+        )));
+    }
+    check_reserved(u32::from_le_bytes(words[4..8].try_into().unwrap()))?;
+    let homology = read_name(reader)?;
+    if !HOMOLOGIES.contains(&homology.as_str()) {
+        return Err(invalid_data(format!("Unknown homology {homology}")));
+    }
+    let filtration = read_name(reader)?;
+    let max_time = read_f64(reader)?;
+    let node_count = read_u64(reader)?;
+    Ok(StoreHeader {
+        version,
+        homology,
+        filtration: (!filtration.is_empty()).then_some(filtration),
+        max_time: max_time.is_finite().then_some(max_time),
+        node_count,
+    })
+}
+
+fn write_name(writer: &mut impl Write, name: &str) -> io::Result<()> {
+    if name.len() > NAME_LEN || name.contains('\0') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Name {name:?} must be at most {NAME_LEN} bytes without zeros"),
+        ));
+    }
+    let mut bytes = [0; NAME_LEN];
+    bytes[..name.len()].copy_from_slice(name.as_bytes());
+    writer.write_all(&bytes)
+}
+
+fn read_name(reader: &mut impl Read) -> io::Result<String> {
+    let mut bytes = [0; NAME_LEN];
+    reader.read_exact(&mut bytes)?;
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(NAME_LEN);
+    String::from_utf8(bytes[..len].to_vec()).map_err(|_| invalid_data("Invalid name".to_string()))
+}
+
+fn check_reserved(value: u32) -> io::Result<()> {
+    if value != 0 {
+        return Err(invalid_data(format!("Reserved field is {value}, not 0")));
+    }
+    Ok(())
+}
+
+// Every section has been read, so the file must end here
+fn check_end(reader: &mut impl Read) -> io::Result<()> {
+    let mut byte = [0; 1];
+    if reader.read(&mut byte)? != 0 {
+        return Err(invalid_data(
+            "Unexpected data after the last section".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Kind code and nodes of a cell, in the order of its Display form
+fn encode_cell(col_type: &ColumnType) -> (u32, [NodeIndex; 4]) {
+    match *col_type {
+        ColumnType::Node(i) => (0, [i, 0, 0, 0]),
+        ColumnType::Edge(i, j) => (1, [i, j, 0, 0]),
+        ColumnType::DoubleEdge(i, j) => (2, [i, j, 0, 0]),
+        ColumnType::Triangle(i, j, k) => (3, [i, j, k, 0]),
+        ColumnType::LongSquare(i, (e0, e1), k) => (4, [i, e0, e1, k]),
+        ColumnType::DoubleSquare(i, (e0, e1)) => (5, [i, e0, e1, 0]),
+    }
+}
+
+fn decode_cell(kind: u32, nodes: [NodeIndex; 4]) -> io::Result<ColumnType> {
+    let [a, b, c, d] = nodes;
+    Ok(match kind {
+        0 => ColumnType::Node(a),
+        1 => ColumnType::Edge(a, b),
+        2 => ColumnType::DoubleEdge(a, b),
+        3 => ColumnType::Triangle(a, b, c),
+        4 => ColumnType::LongSquare(a, (b, c), d),
+        5 => ColumnType::DoubleSquare(a, (b, c)),
+        _ => return Err(invalid_data(format!("Unknown cell kind {kind}"))),
+    })
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_usize(reader: &mut impl Read) -> io::Result<usize> {
+    let value = read_u64(reader)?;
+    usize::try_from(value).map_err(|_| invalid_data(format!("Value {value} is too large")))
+}
+
+fn read_f64(reader: &mut impl Read) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> StoreHeader {
+        StoreHeader::new("rph", 4, Some(FiltrationKind::ShortestPath), Some(2.5)).unwrap()
+    }
+
+    fn cells() -> Vec<GrpphatiRsColumn> {
+        [
+            ColumnType::Node(0),
+            ColumnType::Edge(0, 1),
+            ColumnType::DoubleEdge(1, 2),
+            ColumnType::Triangle(0, 1, 2),
+            ColumnType::LongSquare(0, (1, 2), 3),
+            ColumnType::DoubleSquare(0, (1, 2)),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(idx, col_type)| GrpphatiRsColumn {
+            col_type,
+            entrance_time: (idx != 2).then_some(idx as FiltrationTime),
+        })
+        .collect()
+    }
+
+    fn saved_cells() -> Vec<u8> {
+        let mut bytes = vec![];
+        save_cells(&mut bytes, &cells(), &header()).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn cells_round_trip() {
+        let bytes = saved_cells();
+        assert_eq!(bytes.len(), 64 + 8 + 32 * cells().len());
+        let (read, read_header) = load_cells(bytes.as_slice()).unwrap();
+        assert_eq!(read_header, header());
+        let summary = |cols: &[GrpphatiRsColumn]| -> Vec<_> {
+            cols.iter()
+                .map(|col| (col.col_type, col.entrance_time))
+                .collect()
+        };
+        assert_eq!(summary(&read), summary(&cells()));
+    }
+
+    #[test]
+    fn matrix_round_trips() {
+        let matrix = vec![(0, vec![]), (0, vec![]), (1, vec![0, 1])];
+        let mut bytes = vec![];
+        save_matrix(&mut bytes, &matrix, &header()).unwrap();
+        assert_eq!(bytes.len(), 64 + 16 + 8 * (3 + 4 + 2));
+        assert_eq!(load_matrix(bytes.as_slice()).unwrap(), (matrix, header()));
+    }
+
+    #[test]
+    fn rejects_trailing_data_and_reserved_fields() {
+        let mut bytes = saved_cells();
+        bytes.push(0);
+        assert!(load_cells(bytes.as_slice()).is_err());
+        let mut bytes = saved_cells();
+        bytes[12] = 1;
+        assert!(load_cells(bytes.as_slice()).is_err());
+        // The reserved field of the first cell record
+        let mut bytes = saved_cells();
+        bytes[72 + 28] = 1;
+        assert!(load_cells(bytes.as_slice()).is_err());
+        // Padding after the node of the first cell record
+        let mut bytes = saved_cells();
+        bytes[72 + 16] = 1;
+        assert!(load_cells(bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn rejects_long_names() {
+        let header = StoreHeader {
+            filtration: Some("a_filtration_name_too_long".to_string()),
+            ..header()
+        };
+        let error = save_cells(vec![], &cells(), &header).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
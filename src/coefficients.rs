@@ -0,0 +1,228 @@
+use lophat::utils::PersistenceDiagram;
+
+use crate::Coefficient;
+
+/// A sparse column over Z/p, as row indices paired with non-zero coefficients in `1..p`.
+/// Entries are sorted by row index, so the pivot is the last entry.
+pub type SignedColumn = Vec<(usize, Coefficient)>;
+
+/// The prime field Z/p.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PrimeField {
+    p: Coefficient,
+}
+
+impl PrimeField {
+    /// Fails if `p` is not a prime or is large enough that products could overflow.
+    pub fn new(p: Coefficient) -> Result<Self, String> {
+        if p >= 1 << 31 {
+            return Err(format!(
+                "Characteristic {p} is too large, must be below 2^31"
+            ));
+        }
+        let is_prime = p >= 2 && (2..).take_while(|d| d * d <= p).all(|d| p % d != 0);
+        if !is_prime {
+            return Err(format!("Characteristic {p} is not a prime"));
+        }
+        Ok(Self { p })
+    }
+
+    pub fn characteristic(&self) -> Coefficient {
+        self.p
+    }
+
+    pub fn reduce(&self, a: Coefficient) -> Coefficient {
+        a.rem_euclid(self.p)
+    }
+
+    // Via Fermat's little theorem, a^(p-2) = a^(-1)
+    pub fn inverse(&self, a: Coefficient) -> Coefficient {
+        let mut base = self.reduce(a);
+        assert!(base != 0, "Cannot invert zero");
+        let mut exp = self.p - 2;
+        let mut acc = 1;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                acc = acc * base % self.p;
+            }
+            base = base * base % self.p;
+            exp >>= 1;
+        }
+        acc
+    }
+
+    /// Reduces the coefficients of `column` into `1..p`, summing repeated rows,
+    /// dropping zeros and sorting by row.
+    pub fn normalise(&self, mut column: SignedColumn) -> SignedColumn {
+        column.sort_by_key(|(row, _)| *row);
+        let mut normalised: SignedColumn = Vec::with_capacity(column.len());
+        for (row, coeff) in column {
+            match normalised.last_mut() {
+                Some((last_row, last_coeff)) if *last_row == row => {
+                    *last_coeff = self.reduce(*last_coeff + coeff)
+                }
+                _ => normalised.push((row, self.reduce(coeff))),
+            }
+        }
+        normalised.retain(|(_, coeff)| *coeff != 0);
+        normalised
+    }
+
+    // Computes target + factor * other, with both columns normalised
+    fn add_multiple(
+        &self,
+        target: &SignedColumn,
+        factor: Coefficient,
+        other: &SignedColumn,
+    ) -> SignedColumn {
+        let mut sum = Vec::with_capacity(target.len() + other.len());
+        let (mut t_iter, mut o_iter) = (target.iter().peekable(), other.iter().peekable());
+        loop {
+            match (t_iter.peek(), o_iter.peek()) {
+                (Some(&&(t_row, t_coeff)), Some(&&(o_row, o_coeff))) => {
+                    if t_row < o_row {
+                        sum.push((t_row, t_coeff));
+                        t_iter.next();
+                    } else if o_row < t_row {
+                        sum.push((o_row, factor * o_coeff % self.p));
+                        o_iter.next();
+                    } else {
+                        let coeff = (t_coeff + factor * o_coeff) % self.p;
+                        if coeff != 0 {
+                            sum.push((t_row, coeff));
+                        }
+                        t_iter.next();
+                        o_iter.next();
+                    }
+                }
+                (Some(&&entry), None) => {
+                    sum.push(entry);
+                    t_iter.next();
+                }
+                (None, Some(&&(o_row, o_coeff))) => {
+                    sum.push((o_row, factor * o_coeff % self.p));
+                    o_iter.next();
+                }
+                (None, None) => break,
+            }
+        }
+        sum
+    }
+}
+
+/// Reduces the boundary matrix, given as `(dimension, column)` pairs, over `field`.
+/// Uses the standard algorithm with the clearing optimisation, working down from the top dimension.
+/// Columns must be normalised (see [`PrimeField::normalise`]).
+pub fn decompose_over_field(
+    matrix: Vec<(usize, SignedColumn)>,
+    field: PrimeField,
+) -> PersistenceDiagram {
+    let max_dim = matrix.iter().map(|(dim, _)| *dim).max().unwrap_or(0);
+    let (dimensions, mut columns): (Vec<_>, Vec<_>) = matrix.into_iter().unzip();
+    // pivot_owner[row] is the index of the reduced column with pivot row
+    let mut pivot_owner: Vec<Option<usize>> = vec![None; columns.len()];
+    let mut cleared = vec![false; columns.len()];
+    for working_dim in (0..=max_dim).rev() {
+        for j in 0..columns.len() {
+            if dimensions[j] != working_dim {
+                continue;
+            }
+            if cleared[j] {
+                columns[j].clear();
+                continue;
+            }
+            let mut column = std::mem::take(&mut columns[j]);
+            while let Some(&(pivot, coeff)) = column.last() {
+                let Some(owner) = pivot_owner[pivot] else {
+                    break;
+                };
+                let (_, owner_coeff) = *columns[owner].last().unwrap();
+                let factor = field.reduce(-coeff * field.inverse(owner_coeff));
+                column = field.add_multiple(&column, factor, &columns[owner]);
+            }
+            if let Some(&(pivot, _)) = column.last() {
+                pivot_owner[pivot] = Some(j);
+                // Column at pivot must reduce to zero so we skip it
+                cleared[pivot] = true;
+            }
+            columns[j] = column;
+        }
+    }
+    let mut diagram = PersistenceDiagram::default();
+    for (j, column) in columns.iter().enumerate() {
+        if let Some(&(pivot, _)) = column.last() {
+            diagram.paired.insert((pivot, j));
+        } else if pivot_owner[j].is_none() {
+            diagram.unpaired.insert(j);
+        }
+    }
+    diagram
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_non_primes() {
+        assert!(PrimeField::new(1).is_err());
+        assert!(PrimeField::new(4).is_err());
+        assert!(PrimeField::new(1 << 31).is_err());
+        assert_eq!(PrimeField::new(7).unwrap().characteristic(), 7);
+    }
+
+    #[test]
+    fn inverts_modulo_p() {
+        let field = PrimeField::new(7).unwrap();
+        for a in 1..7 {
+            assert_eq!(a * field.inverse(a) % 7, 1);
+        }
+        assert_eq!(field.inverse(-1), 6);
+    }
+
+    #[test]
+    fn normalise_sums_repeated_rows() {
+        let field = PrimeField::new(3).unwrap();
+        let column = vec![(2, 1), (0, -1), (2, 2), (1, 4)];
+        assert_eq!(field.normalise(column), vec![(0, 2), (1, 1)]);
+    }
+
+    // Nodes 0, 1, 2, edges 3 = 01, 4 = 02, 5 = 12 and the triangle 6 = 012
+    #[test]
+    fn reduces_a_filled_triangle() {
+        let field = PrimeField::new(3).unwrap();
+        let matrix = vec![
+            (0, vec![]),
+            (0, vec![]),
+            (0, vec![]),
+            (1, vec![(0, -1), (1, 1)]),
+            (1, vec![(0, -1), (2, 1)]),
+            (1, vec![(1, -1), (2, 1)]),
+            (2, vec![(3, 1), (4, -1), (5, 1)]),
+        ];
+        let matrix = matrix
+            .into_iter()
+            .map(|(dim, col)| (dim, field.normalise(col)))
+            .collect();
+        let diagram = decompose_over_field(matrix, field);
+        assert_eq!(diagram.unpaired, [0].into_iter().collect());
+        assert_eq!(
+            diagram.paired,
+            [(1, 3), (2, 4), (5, 6)].into_iter().collect()
+        );
+    }
+
+    // A cell whose boundary is twice a node is a cycle only over Z/2
+    #[test]
+    fn depends_on_the_characteristic() {
+        let matrix = |field: PrimeField| vec![(0, vec![]), (1, field.normalise(vec![(0, 2)]))];
+        let z2 = PrimeField::new(2).unwrap();
+        let diagram = decompose_over_field(matrix(z2), z2);
+        assert_eq!(diagram.unpaired, [0, 1].into_iter().collect());
+        assert!(diagram.paired.is_empty());
+        let z3 = PrimeField::new(3).unwrap();
+        let diagram = decompose_over_field(matrix(z3), z3);
+        assert!(diagram.unpaired.is_empty());
+        assert_eq!(diagram.paired, [(0, 1)].into_iter().collect());
+    }
+}
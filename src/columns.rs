@@ -1,19 +1,95 @@
 use crate::homology::TwoPathType;
-use crate::{FiltrationTime, NodeIndex};
-use pyo3::prelude::*;
+use crate::{Coefficient, FiltrationTime, NodeIndex};
+use pyo3::{exceptions::PyValueError, prelude::*};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::hash_map::DefaultHasher;
+use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::str::FromStr;
 
 // TODO: This would be better implemented as a trait, does this play well with PyO3?
-#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+// The derived order is only used to break ties between cells entering at the same time
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
 pub enum ColumnType {
     DoubleEdge(NodeIndex, NodeIndex),          // (i,j) where i → j → i
     Triangle(NodeIndex, NodeIndex, NodeIndex), // (i, j, k) where i → j → k, i → k
     LongSquare(NodeIndex, (NodeIndex, NodeIndex), NodeIndex), // (i, (j, l), k) i → j → k, i → l → k, i ↛ k
+    DoubleSquare(NodeIndex, (NodeIndex, NodeIndex)), // (i, (j, l)) i → j → i - i → l → i, non-regular only
     Edge(NodeIndex, NodeIndex),
     Node(NodeIndex),
 }
 
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColumnType::DoubleEdge(i, j) => write!(f, "DoubleEdge({i},{j})"),
+            ColumnType::Triangle(i, j, k) => write!(f, "Triangle({i},{j},{k})"),
+            ColumnType::LongSquare(i, (e0, e1), k) => write!(f, "LongSquare({i},{e0},{e1},{k})"),
+            ColumnType::DoubleSquare(i, (e0, e1)) => write!(f, "DoubleSquare({i},{e0},{e1})"),
+            ColumnType::Edge(i, j) => write!(f, "Edge({i},{j})"),
+            ColumnType::Node(i) => write!(f, "Node({i})"),
+        }
+    }
+}
+
+impl ColumnType {
+    /// Builds a column type from its name and nodes, listed in the order they appear in its `Display` form.
+    pub fn from_parts(name: &str, data: &[NodeIndex]) -> Result<Self, String> {
+        let expected = match name {
+            "DoubleEdge" | "Edge" => 2,
+            "Triangle" | "DoubleSquare" => 3,
+            "LongSquare" => 4,
+            "Node" => 1,
+            _ => return Err(format!("Unknown column type {name}")),
+        };
+        if data.len() != expected {
+            return Err(format!(
+                "{name} needs {expected} nodes but {} were given",
+                data.len()
+            ));
+        }
+        Ok(match name {
+            "DoubleEdge" => ColumnType::DoubleEdge(data[0], data[1]),
+            "Triangle" => ColumnType::Triangle(data[0], data[1], data[2]),
+            "LongSquare" => ColumnType::LongSquare(data[0], (data[1], data[2]), data[3]),
+            "DoubleSquare" => ColumnType::DoubleSquare(data[0], (data[1], data[2])),
+            "Edge" => ColumnType::Edge(data[0], data[1]),
+            _ => ColumnType::Node(data[0]),
+        })
+    }
+}
+
+// Inverse of the Display impl, e.g. "Triangle(0,1,2)"
+impl FromStr for ColumnType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid column type {s}");
+        let (name, rest) = s.trim().split_once('(').ok_or_else(invalid)?;
+        let data = rest
+            .strip_suffix(')')
+            .ok_or_else(invalid)?
+            .split(',')
+            .map(|node| node.trim().parse::<NodeIndex>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>, _>>()?;
+        ColumnType::from_parts(name, &data)
+    }
+}
+
+// Serialized in the Display form so that cells are readable in both JSON and CSV
+impl Serialize for ColumnType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ColumnType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 impl TryFrom<TwoPathType> for ColumnType {
     type Error = &'static str;
     fn try_from(value: TwoPathType) -> Result<Self, Self::Error> {
@@ -26,7 +102,7 @@ impl TryFrom<TwoPathType> for ColumnType {
 }
 
 #[pyclass]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GrpphatiRsColumn {
     pub col_type: ColumnType,
     pub entrance_time: Option<FiltrationTime>,
@@ -39,20 +115,13 @@ impl GrpphatiRsColumn {
         col_type_str: &str,
         data: Vec<NodeIndex>,
         entrance_time: Option<FiltrationTime>,
-    ) -> Self {
-        // TODO: Deal with incorrectly supplied data
-        let col_type = match col_type_str {
-            "DoubleEdge" => ColumnType::DoubleEdge(data[0], data[1]),
-            "Triangle" => ColumnType::Triangle(data[0], data[1], data[2]),
-            "LongSquare" => ColumnType::LongSquare(data[0], (data[1], data[2]), data[3]),
-            "Edge" => ColumnType::Edge(data[0], data[1]),
-            "Node" => ColumnType::Node(data[0]),
-            _ => panic!(),
-        };
-        Self {
+    ) -> PyResult<Self> {
+        let col_type =
+            ColumnType::from_parts(col_type_str, &data).map_err(PyValueError::new_err)?;
+        Ok(Self {
             col_type,
             entrance_time,
-        }
+        })
     }
 
     pub fn dimension(&self) -> usize {
@@ -60,6 +129,7 @@ impl GrpphatiRsColumn {
             ColumnType::DoubleEdge(_, _) => 2,
             ColumnType::Triangle(_, _, _) => 2,
             ColumnType::LongSquare(_, _, _) => 2,
+            ColumnType::DoubleSquare(_, _) => 2,
             ColumnType::Edge(_, _) => 1,
             ColumnType::Node(_) => 0,
         }
@@ -72,27 +142,20 @@ impl GrpphatiRsColumn {
 
     // TODO: Make this more informative
     fn __repr__(&self) -> String {
-        match self.col_type {
-            ColumnType::DoubleEdge(i, j) => format!("DoubleEdge({i},{j})"),
-            ColumnType::Triangle(i, j, k) => format!("Triangle({i},{j},{k})"),
-            ColumnType::LongSquare(i, (e0, e1), k) => format!("LongSquare({i},{e0},{e1},{k})"),
-            ColumnType::Edge(i, j) => format!("Edge({i},{j})"),
-            ColumnType::Node(i) => format!("Node({i})"),
-        }
+        self.col_type.to_string()
     }
 
     fn __eq__(&self, other: &PyAny) -> bool {
         other
             .extract()
-            .and_then(|other_col: GrpphatiRsColumn| Ok(other_col.col_type == self.col_type))
+            .map(|other_col: GrpphatiRsColumn| other_col.col_type == self.col_type)
             .unwrap_or(false)
     }
 
     fn __hash__(&self) -> u64 {
         let mut hasher = DefaultHasher::new();
         self.col_type.hash(&mut hasher);
-        let output = hasher.finish();
-        output
+        hasher.finish()
     }
 
     // Defining this magic method becuase __eq__ is not supported by PyO3
@@ -108,58 +171,50 @@ impl GrpphatiRsColumn {
     }
 
     pub fn boundary(&self) -> Vec<GrpphatiRsColumn> {
+        self.signed_boundary()
+            .into_iter()
+            .map(|(face, _coeff)| face)
+            .collect()
+    }
+
+    /// Boundary of the column with orientation signs, as integer coefficients.
+    /// Degenerate terms (e.g. i → i in the boundary of a double edge) are dropped,
+    /// as in regular path homology.
+    pub fn signed_boundary(&self) -> Vec<(GrpphatiRsColumn, Coefficient)> {
+        let face = |col_type| GrpphatiRsColumn {
+            col_type,
+            entrance_time: None,
+        };
         match self.col_type {
+            // ∂(iji) = ij + ji
             ColumnType::DoubleEdge(i, j) => vec![
-                GrpphatiRsColumn {
-                    col_type: ColumnType::Edge(i, j),
-                    entrance_time: None,
-                },
-                GrpphatiRsColumn {
-                    col_type: ColumnType::Edge(j, i),
-                    entrance_time: None,
-                },
+                (face(ColumnType::Edge(i, j)), 1),
+                (face(ColumnType::Edge(j, i)), 1),
             ],
+            // ∂(ijk) = ij + jk - ik
             ColumnType::Triangle(i, j, k) => vec![
-                GrpphatiRsColumn {
-                    col_type: ColumnType::Edge(i, j),
-                    entrance_time: None,
-                },
-                GrpphatiRsColumn {
-                    col_type: ColumnType::Edge(j, k),
-                    entrance_time: None,
-                },
-                GrpphatiRsColumn {
-                    col_type: ColumnType::Edge(i, k),
-                    entrance_time: None,
-                },
+                (face(ColumnType::Edge(i, j)), 1),
+                (face(ColumnType::Edge(j, k)), 1),
+                (face(ColumnType::Edge(i, k)), -1),
             ],
+            // ∂(ijk - ilk) = ij + jk - il - lk
             ColumnType::LongSquare(start, midpoints, end) => vec![
-                GrpphatiRsColumn {
-                    col_type: ColumnType::Edge(start, midpoints.0),
-                    entrance_time: None,
-                },
-                GrpphatiRsColumn {
-                    col_type: ColumnType::Edge(start, midpoints.1),
-                    entrance_time: None,
-                },
-                GrpphatiRsColumn {
-                    col_type: ColumnType::Edge(midpoints.0, end),
-                    entrance_time: None,
-                },
-                GrpphatiRsColumn {
-                    col_type: ColumnType::Edge(midpoints.1, end),
-                    entrance_time: None,
-                },
+                (face(ColumnType::Edge(start, midpoints.0)), 1),
+                (face(ColumnType::Edge(start, midpoints.1)), -1),
+                (face(ColumnType::Edge(midpoints.0, end)), 1),
+                (face(ColumnType::Edge(midpoints.1, end)), -1),
+            ],
+            // ∂(iji - ili) = ij + ji - il - li, the degenerate terms ii cancel
+            ColumnType::DoubleSquare(i, midpoints) => vec![
+                (face(ColumnType::Edge(i, midpoints.0)), 1),
+                (face(ColumnType::Edge(midpoints.0, i)), 1),
+                (face(ColumnType::Edge(i, midpoints.1)), -1),
+                (face(ColumnType::Edge(midpoints.1, i)), -1),
             ],
+            // ∂(ij) = j - i
             ColumnType::Edge(i, j) => vec![
-                GrpphatiRsColumn {
-                    col_type: ColumnType::Node(i),
-                    entrance_time: None,
-                },
-                GrpphatiRsColumn {
-                    col_type: ColumnType::Node(j),
-                    entrance_time: None,
-                },
+                (face(ColumnType::Node(i)), -1),
+                (face(ColumnType::Node(j)), 1),
             ],
             ColumnType::Node(_) => vec![],
         }
@@ -185,6 +240,12 @@ impl GrpphatiRsColumn {
                     let args = (i, midpoints, j, self.entrance_time);
                     col_cls.call1(args).unwrap().into_py(py)
                 }
+                // A long square from i to i has the same boundary over Z/2
+                ColumnType::DoubleSquare(i, midpoints) => {
+                    let col_cls = columns.getattr("LongSquareCol").unwrap();
+                    let args = (i, midpoints, i, self.entrance_time);
+                    col_cls.call1(args).unwrap().into_py(py)
+                }
                 ColumnType::Edge(i, j) => {
                     let col_cls = columns.getattr("EdgeCol").unwrap();
                     let args = ((i, j), self.entrance_time);
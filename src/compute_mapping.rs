@@ -1,130 +1,497 @@
 // Placeholder
 
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
 
 use itertools::Itertools;
 
 use dashmap::DashMap;
 
-use pyo3::prelude::*;
+use pyo3::{exceptions::PyValueError, prelude::*};
 
 use crate::{
     columns::{ColumnType, GrpphatiRsColumn},
-    NodeIndex,
+    FiltrationTime, NodeIndex,
 };
 
 use rayon::prelude::*;
 
 type NodeMapping = HashMap<NodeIndex, NodeIndex>;
 
+/// Reasons for which a vertex map fails to induce a chain map between two cell lists.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainMapError {
+    /// A vertex of `cell` does not appear in the vertex map.
+    MissingVertex { cell: ColumnType, vertex: NodeIndex },
+    /// The image of `cell` requires `image`, which is not in the codomain.
+    MissingImage { cell: ColumnType, image: ColumnType },
+    /// `cell` is sent to `image`, which has a different dimension.
+    DimensionMismatch { cell: ColumnType, image: ColumnType },
+    /// `cell` is sent to `image`, which enters the codomain filtration later than `cell`.
+    FiltrationViolation {
+        cell: ColumnType,
+        cell_time: FiltrationTime,
+        image: ColumnType,
+        image_time: FiltrationTime,
+    },
+    /// `cell` contains an edge whose endpoints are identified, which is only allowed in regular path homology.
+    CollapsedEdge { cell: ColumnType },
+    /// The boundary of the image of `cell` differs from the image of its boundary.
+    NonCommuting {
+        cell: ColumnType,
+        boundary_of_image: Vec<ColumnType>,
+        image_of_boundary: Vec<ColumnType>,
+    },
+}
+
+impl fmt::Display for ChainMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainMapError::MissingVertex { cell, vertex } => {
+                write!(f, "{cell}: vertex {vertex} is not in the vertex map")
+            }
+            ChainMapError::MissingImage { cell, image } => {
+                write!(
+                    f,
+                    "{cell}: image requires {image}, which is not in the codomain"
+                )
+            }
+            ChainMapError::DimensionMismatch { cell, image } => {
+                write!(f, "{cell}: sent to {image} of a different dimension")
+            }
+            ChainMapError::FiltrationViolation {
+                cell,
+                cell_time,
+                image,
+                image_time,
+            } => write!(
+                f,
+                "{cell}: enters at {cell_time} but its image {image} enters at {image_time}"
+            ),
+            ChainMapError::CollapsedEdge { cell } => write!(
+                f,
+                "{cell}: collapses an edge, which non-regular path homology does not allow"
+            ),
+            ChainMapError::NonCommuting {
+                cell,
+                boundary_of_image,
+                image_of_boundary,
+            } => write!(
+                f,
+                "{cell}: boundary of image [{}] differs from image of boundary [{}]",
+                boundary_of_image.iter().join(", "),
+                image_of_boundary.iter().join(", ")
+            ),
+        }
+    }
+}
+
+/// The image of each of `domain_cells` under the chain map induced by `vertex_map`, as indices into `codomain_cells`.
+/// Raises a `ValueError` listing every cell whose image cannot be found.
 #[pyfunction]
 #[pyo3(name = "compute_rph_map")]
 pub fn compute_map_py(
     domain_cells: Vec<GrpphatiRsColumn>,
     codomain_cells: Vec<GrpphatiRsColumn>,
     vertex_map: NodeMapping,
-) -> Vec<Vec<usize>> {
-    compute_map(&domain_cells, &codomain_cells, vertex_map)
+) -> PyResult<Vec<Vec<usize>>> {
+    compute_map(&domain_cells, &codomain_cells, vertex_map).map_err(map_errors_to_py)
+}
+
+/// Checks that `vertex_map` induces a filtration-preserving chain map from `domain_cells`
+/// to `codomain_cells`, raising a `ValueError` listing every offending cell otherwise.
+#[pyfunction]
+#[pyo3(name = "verify_rph_chain_map")]
+pub fn verify_chain_map_py(
+    domain_cells: Vec<GrpphatiRsColumn>,
+    codomain_cells: Vec<GrpphatiRsColumn>,
+    vertex_map: NodeMapping,
+) -> PyResult<()> {
+    verify_chain_map(&domain_cells, &codomain_cells, &vertex_map).map_err(map_errors_to_py)
 }
 
+/// As `compute_rph_map` but for the non-regular path complexes.
+#[pyfunction]
+#[pyo3(name = "compute_nrph_map")]
+pub fn compute_nrph_map_py(
+    domain_cells: Vec<GrpphatiRsColumn>,
+    codomain_cells: Vec<GrpphatiRsColumn>,
+    vertex_map: NodeMapping,
+) -> PyResult<Vec<Vec<usize>>> {
+    compute_nrph_map(&domain_cells, &codomain_cells, vertex_map).map_err(map_errors_to_py)
+}
+
+fn map_errors_to_py(errors: Vec<ChainMapError>) -> PyErr {
+    PyValueError::new_err(format!(
+        "Vertex map does not induce a chain map:\n{}",
+        errors.iter().join("\n")
+    ))
+}
+
+/// As `verify_rph_chain_map` but for the non-regular path complexes.
+#[pyfunction]
+#[pyo3(name = "verify_nrph_chain_map")]
+pub fn verify_nrph_chain_map_py(
+    domain_cells: Vec<GrpphatiRsColumn>,
+    codomain_cells: Vec<GrpphatiRsColumn>,
+    vertex_map: NodeMapping,
+) -> PyResult<()> {
+    verify_nrph_chain_map(&domain_cells, &codomain_cells, &vertex_map).map_err(map_errors_to_py)
+}
+
+/// The image of each of `dflag_cells` under the inclusion into the regular path complex `rph_cells`
+/// of the same filtration, as indices into `rph_cells`.
+/// Raises a `ValueError` listing every offending cell if this is not a filtration-preserving chain map,
+/// e.g. because the cells were built from different filtrations.
+#[pyfunction]
+#[pyo3(name = "compute_dflag_inclusion")]
+pub fn compute_dflag_inclusion_py(
+    dflag_cells: Vec<GrpphatiRsColumn>,
+    rph_cells: Vec<GrpphatiRsColumn>,
+) -> PyResult<Vec<Vec<usize>>> {
+    compute_dflag_inclusion(&dflag_cells, &rph_cells).map_err(|errors| {
+        PyValueError::new_err(format!(
+            "Cells do not include into the regular path complex:\n{}",
+            errors.iter().join("\n")
+        ))
+    })
+}
+
+/// The sorted image of each of `domain_cells` as indices into `codomain_cells`,
+/// or every error met in computing the images, e.g. a missing vertex or image.
+/// The map is not otherwise checked, see [`verify_chain_map`].
 pub fn compute_map(
-    domain_cells: &Vec<GrpphatiRsColumn>,
-    codomain_cells: &Vec<GrpphatiRsColumn>,
+    domain_cells: &[GrpphatiRsColumn],
+    codomain_cells: &[GrpphatiRsColumn],
+    vertex_map: NodeMapping,
+) -> Result<Vec<Vec<usize>>, Vec<ChainMapError>> {
+    compute_path_map(domain_cells, codomain_cells, vertex_map, true)
+}
+
+/// As [`compute_map`] but between cells produced by `get_nrph_two_cells`,
+/// where a vertex map collapsing an edge does not induce a chain map.
+pub fn compute_nrph_map(
+    domain_cells: &[GrpphatiRsColumn],
+    codomain_cells: &[GrpphatiRsColumn],
     vertex_map: NodeMapping,
-) -> Vec<Vec<usize>> {
-    let index = build_index(codomain_cells);
-    domain_cells
+) -> Result<Vec<Vec<usize>>, Vec<ChainMapError>> {
+    compute_path_map(domain_cells, codomain_cells, vertex_map, false)
+}
+
+fn compute_path_map(
+    domain_cells: &[GrpphatiRsColumn],
+    codomain_cells: &[GrpphatiRsColumn],
+    vertex_map: NodeMapping,
+    regular: bool,
+) -> Result<Vec<Vec<usize>>, Vec<ChainMapError>> {
+    let index = build_index(codomain_cells, regular);
+    let images: Vec<_> = domain_cells
         .par_iter()
-        .map(|col| match col.col_type {
-            ColumnType::Triangle(i, j, k) => {
-                let fi = *vertex_map.get(&i).unwrap();
-                let fj = *vertex_map.get(&j).unwrap();
-                let fk = *vertex_map.get(&k).unwrap();
-                let image_set = compute_two_path_image(&index, (fi, fj, fk));
-                image_set.into_iter().sorted().collect()
-            }
-            ColumnType::LongSquare(s, mids, t) => {
-                let fs = *vertex_map.get(&s).unwrap();
-                let fu = *vertex_map.get(&mids.0).unwrap();
-                let fv = *vertex_map.get(&mids.1).unwrap();
-                let ft = *vertex_map.get(&t).unwrap();
-                let path_1 = (fs, fu, ft);
-                let path_2 = (fs, fv, ft);
-                let im_1 = compute_two_path_image(&index, path_1);
-                let im_2 = compute_two_path_image(&index, path_2);
-                let final_im = im_1.symmetric_difference(&im_2);
-                final_im.into_iter().cloned().sorted().collect()
-            }
-            ColumnType::DoubleEdge(i, j) => {
-                let fi = *vertex_map.get(&i).unwrap();
-                let fj = *vertex_map.get(&j).unwrap();
-                let image_set = compute_two_path_image(&index, (fi, fj, fi));
-                image_set.into_iter().sorted().collect()
-            }
-            ColumnType::Edge(i, j) => {
-                let fi = *vertex_map.get(&i).unwrap();
-                let fj = *vertex_map.get(&j).unwrap();
-                if fi == fj {
-                    vec![]
-                } else {
-                    let im_idx = index.edges.get(&(fi, fj)).unwrap().value().clone();
-                    vec![im_idx]
+        .map(|col| compute_cell_image(&index, &vertex_map, col.col_type))
+        .collect();
+    let errors: Vec<_> = images
+        .iter()
+        .filter_map(|image| image.as_ref().err().cloned())
+        .collect();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(images
+        .into_iter()
+        .map(|image| image.unwrap().into_iter().sorted().collect())
+        .collect())
+}
+
+/// The inclusion of the directed flag complex into the regular path complex of the same filtration,
+/// i.e. the chain map induced by the identity on vertices, after verifying it as in [`verify_chain_map`].
+/// A directed triangle i → j → k is a regular 2-path, but when several share the endpoints i and k,
+/// all but one are sent to a long square plus the triangle through the base midpoint.
+pub fn compute_dflag_inclusion(
+    dflag_cells: &[GrpphatiRsColumn],
+    rph_cells: &[GrpphatiRsColumn],
+) -> Result<Vec<Vec<usize>>, Vec<ChainMapError>> {
+    let vertex_map: NodeMapping = dflag_cells
+        .iter()
+        .chain(rph_cells.iter())
+        .filter_map(|col| match col.col_type {
+            ColumnType::Node(node) => Some((node, node)),
+            _ => None,
+        })
+        .collect();
+    verify_chain_map(dflag_cells, rph_cells, &vertex_map)?;
+    compute_map(dflag_cells, rph_cells, vertex_map)
+}
+
+/// Verifies that `vertex_map` induces a chain map from `domain_cells` to `codomain_cells`.
+/// We check that every image lands in the codomain, preserves dimension,
+/// enters no later than its preimage and that the map commutes with the boundaries
+/// given by [`GrpphatiRsColumn::boundary`] (over Z/2).
+pub fn verify_chain_map(
+    domain_cells: &[GrpphatiRsColumn],
+    codomain_cells: &[GrpphatiRsColumn],
+    vertex_map: &NodeMapping,
+) -> Result<(), Vec<ChainMapError>> {
+    verify_path_chain_map(domain_cells, codomain_cells, vertex_map, true)
+}
+
+/// As [`verify_chain_map`] but between cells produced by `get_nrph_two_cells`.
+pub fn verify_nrph_chain_map(
+    domain_cells: &[GrpphatiRsColumn],
+    codomain_cells: &[GrpphatiRsColumn],
+    vertex_map: &NodeMapping,
+) -> Result<(), Vec<ChainMapError>> {
+    verify_path_chain_map(domain_cells, codomain_cells, vertex_map, false)
+}
+
+fn verify_path_chain_map(
+    domain_cells: &[GrpphatiRsColumn],
+    codomain_cells: &[GrpphatiRsColumn],
+    vertex_map: &NodeMapping,
+    regular: bool,
+) -> Result<(), Vec<ChainMapError>> {
+    let index = build_index(codomain_cells, regular);
+    let errors: Vec<_> = domain_cells
+        .par_iter()
+        .flat_map_iter(|col| verify_cell(&index, codomain_cells, vertex_map, col))
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn verify_cell(
+    index: &CodomainIndex,
+    codomain_cells: &[GrpphatiRsColumn],
+    vertex_map: &NodeMapping,
+    col: &GrpphatiRsColumn,
+) -> Vec<ChainMapError> {
+    let image = match compute_cell_image(index, vertex_map, col.col_type) {
+        Ok(image) => image,
+        Err(err) => return vec![err],
+    };
+    let mut errors = vec![];
+    for &im_idx in image.iter() {
+        let im_col = &codomain_cells[im_idx];
+        // Images are looked up by cell type, so this only fails if the index disagrees with the cells
+        if im_col.dimension() != col.dimension() {
+            errors.push(ChainMapError::DimensionMismatch {
+                cell: col.col_type,
+                image: im_col.col_type,
+            });
+        }
+        if let (Some(cell_time), Some(image_time)) = (col.entrance_time, im_col.entrance_time) {
+            if image_time > cell_time {
+                errors.push(ChainMapError::FiltrationViolation {
+                    cell: col.col_type,
+                    cell_time,
+                    image: im_col.col_type,
+                    image_time,
+                });
+            }
+        }
+    }
+    // Boundary of the image, computed in the codomain
+    let n_errors = errors.len();
+    let mut boundary_of_image = HashSet::new();
+    for &im_idx in image.iter() {
+        for face in codomain_cells[im_idx].boundary() {
+            match index.lookup(face.col_type) {
+                Some(face_idx) => toggle(&mut boundary_of_image, face_idx),
+                None => errors.push(ChainMapError::MissingImage {
+                    cell: codomain_cells[im_idx].col_type,
+                    image: face.col_type,
+                }),
+            }
+        }
+    }
+    // Image of the boundary, computed from the domain faces
+    let mut image_of_boundary = HashSet::new();
+    for face in col.boundary() {
+        match compute_cell_image(index, vertex_map, face.col_type) {
+            Ok(face_image) => {
+                for face_idx in face_image {
+                    toggle(&mut image_of_boundary, face_idx)
                 }
             }
-            ColumnType::Node(i) => {
-                let fi = *vertex_map.get(&i).unwrap();
-                let im_idx = index.nodes.get(&fi).unwrap().value().clone();
-                vec![im_idx]
+            Err(err) => errors.push(err),
+        }
+    }
+    // Only compare boundaries if every face could be resolved
+    if errors.len() == n_errors && boundary_of_image != image_of_boundary {
+        let describe = |idxs: HashSet<usize>| {
+            idxs.into_iter()
+                .sorted()
+                .map(|idx| codomain_cells[idx].col_type)
+                .collect()
+        };
+        errors.push(ChainMapError::NonCommuting {
+            cell: col.col_type,
+            boundary_of_image: describe(boundary_of_image),
+            image_of_boundary: describe(image_of_boundary),
+        });
+    }
+    errors
+}
+
+// Addition over Z/2
+fn toggle(set: &mut HashSet<usize>, idx: usize) {
+    if !set.remove(&idx) {
+        set.insert(idx);
+    }
+}
+
+fn map_vertex(
+    vertex_map: &NodeMapping,
+    cell: ColumnType,
+    vertex: NodeIndex,
+) -> Result<NodeIndex, ChainMapError> {
+    vertex_map
+        .get(&vertex)
+        .copied()
+        .ok_or(ChainMapError::MissingVertex { cell, vertex })
+}
+
+fn compute_cell_image(
+    index: &CodomainIndex,
+    vertex_map: &NodeMapping,
+    cell: ColumnType,
+) -> Result<HashSet<usize>, ChainMapError> {
+    let f = |vertex| map_vertex(vertex_map, cell, vertex);
+    match cell {
+        ColumnType::Triangle(i, j, k) => compute_two_path_image(index, cell, (f(i)?, f(j)?, f(k)?)),
+        ColumnType::LongSquare(s, mids, t) => {
+            let (fs, fu, fv, ft) = (f(s)?, f(mids.0)?, f(mids.1)?, f(t)?);
+            let path_1 = (fs, fu, ft);
+            let path_2 = (fs, fv, ft);
+            let im_1 = compute_two_path_image(index, cell, path_1)?;
+            let im_2 = compute_two_path_image(index, cell, path_2)?;
+            Ok(im_1.symmetric_difference(&im_2).cloned().collect())
+        }
+        ColumnType::DoubleSquare(i, mids) => {
+            let (fi, fj, fl) = (f(i)?, f(mids.0)?, f(mids.1)?);
+            let im_1 = compute_two_path_image(index, cell, (fi, fj, fi))?;
+            let im_2 = compute_two_path_image(index, cell, (fi, fl, fi))?;
+            Ok(im_1.symmetric_difference(&im_2).cloned().collect())
+        }
+        ColumnType::DoubleEdge(i, j) => {
+            let fi = f(i)?;
+            let fj = f(j)?;
+            compute_two_path_image(index, cell, (fi, fj, fi))
+        }
+        ColumnType::Edge(i, j) => {
+            let fi = f(i)?;
+            let fj = f(j)?;
+            if fi == fj && !index.regular {
+                Err(ChainMapError::CollapsedEdge { cell })
+            } else if fi == fj {
+                Ok(HashSet::default())
+            } else {
+                let image = ColumnType::Edge(fi, fj);
+                let im_idx = index
+                    .lookup(image)
+                    .ok_or(ChainMapError::MissingImage { cell, image })?;
+                Ok(HashSet::from([im_idx]))
             }
-        })
-        .collect()
+        }
+        ColumnType::Node(i) => {
+            let image = ColumnType::Node(f(i)?);
+            let im_idx = index
+                .lookup(image)
+                .ok_or(ChainMapError::MissingImage { cell, image })?;
+            Ok(HashSet::from([im_idx]))
+        }
+    }
 }
 
 // Remember to sort output before returning vector
 fn compute_two_path_image(
     index: &CodomainIndex,
+    cell: ColumnType,
     image_path: (NodeIndex, NodeIndex, NodeIndex),
-) -> HashSet<usize> {
+) -> Result<HashSet<usize>, ChainMapError> {
+    let collapsed = image_path.0 == image_path.1 || image_path.1 == image_path.2;
+    if collapsed && !index.regular {
+        return Err(ChainMapError::CollapsedEdge { cell });
+    }
+    if image_path.0 == image_path.2 && !index.regular {
+        // Image is i → j → i, which is not a cell by itself, so we express it as
+        // i → (base) → i - DoubleSquare(i, (base, j)).
+        // The base terms cancel in the image of any cell.
+        // Without double squares at i, the image must be the only double edge at i, which is the base
+        let base_node = match index.loop_bases.get(&image_path.0) {
+            Some(base) => *base,
+            None => {
+                let (i, j) = (image_path.0, image_path.1);
+                if index.lookup(ColumnType::Edge(i, j)).is_none()
+                    || index.lookup(ColumnType::Edge(j, i)).is_none()
+                {
+                    return Err(ChainMapError::MissingImage {
+                        cell,
+                        image: ColumnType::DoubleEdge(i, j),
+                    });
+                }
+                j
+            }
+        };
+        if base_node == image_path.1 {
+            return Ok(HashSet::default());
+        }
+        let image = ColumnType::DoubleSquare(image_path.0, (base_node, image_path.1));
+        let im_idx = index
+            .lookup(image)
+            .ok_or(ChainMapError::MissingImage { cell, image })?;
+        return Ok(HashSet::from([im_idx]));
+    }
     if image_path.0 == image_path.2 {
         if image_path.0 == image_path.1 {
             // Path is collapsed to nothing
-            return HashSet::default();
+            return Ok(HashSet::default());
         }
         // Image is a double edge
+        let image = ColumnType::DoubleEdge(image_path.0, image_path.1);
         let im_idx = index
-            .double_edges
-            .get(&(image_path.0, image_path.1))
-            .unwrap()
-            .value()
-            .clone();
-        return HashSet::from([im_idx]);
+            .lookup(image)
+            .ok_or(ChainMapError::MissingImage { cell, image })?;
+        return Ok(HashSet::from([im_idx]));
     }
-    if image_path.0 == image_path.1 || image_path.1 == image_path.2 {
-        return HashSet::default();
+    if collapsed {
+        return Ok(HashSet::default());
     }
     // Image is a two-path with all distinct vertices
     // Must be combination of long square and directed triangles
-    if index.triangles.contains_key(&image_path) {
-        let im_idx = index.triangles.get(&image_path).unwrap().value().clone();
-        return HashSet::from([im_idx]);
+    if let Some(im_idx) = index.triangles.get(&image_path) {
+        return Ok(HashSet::from([*im_idx]));
     }
     // Image must be contained in a long square
     // We fetch the index of that long square and the index of the triangle corresponding
     // to the other half of the long square
-    let ls_idx = index.long_squares.get(&image_path).unwrap().value().clone();
-    let base_node = index
+    let missing = ChainMapError::MissingImage {
+        cell,
+        image: ColumnType::Triangle(image_path.0, image_path.1, image_path.2),
+    };
+    let base_node = *index
         .bases
         .get(&(image_path.0, image_path.2))
-        .unwrap()
-        .value()
-        .clone();
-    let base_idx = index
+        .ok_or_else(|| missing.clone())?;
+    // If the endpoints are never joined there is no such triangle,
+    // but then the base terms cancel in the image of any cell
+    let mut image = HashSet::new();
+    if let Some(base_idx) = index
         .triangles
         .get(&(image_path.0, base_node, image_path.2))
-        .unwrap()
-        .value()
-        .clone();
-    HashSet::from([ls_idx, base_idx])
+    {
+        image.insert(*base_idx);
+    }
+    if base_node == image_path.1 {
+        return Ok(image);
+    }
+    let ls_idx = *index.long_squares.get(&image_path).ok_or(missing)?;
+    image.insert(ls_idx);
+    Ok(image)
 }
 
 #[derive(Default)]
@@ -140,10 +507,43 @@ struct CodomainIndex {
     long_squares: DashMap<(NodeIndex, NodeIndex, NodeIndex), usize>,
     // Stores the index of a triangle a -> b -> c in triangles[(a,b,c)]
     triangles: DashMap<(NodeIndex, NodeIndex, NodeIndex), usize>,
+    // Whether the codomain is a regular path complex
+    regular: bool,
+    // For a node i, loop_bases[i] is the starting midpoint of all double squares at i
+    loop_bases: DashMap<NodeIndex, NodeIndex>,
+    // For a node i, double_squares[(i, m)] is the index of double square imi - i(base)i
+    double_squares: DashMap<(NodeIndex, NodeIndex), usize>,
 }
 
-fn build_index(codomain_cells: &Vec<GrpphatiRsColumn>) -> CodomainIndex {
-    let index = CodomainIndex::default();
+impl CodomainIndex {
+    // Returns the index of the column with the given type, if present
+    fn lookup(&self, col_type: ColumnType) -> Option<usize> {
+        match col_type {
+            ColumnType::Node(i) => self.nodes.get(&i).map(|idx| *idx),
+            ColumnType::Edge(i, j) => self.edges.get(&(i, j)).map(|idx| *idx),
+            ColumnType::DoubleEdge(i, j) => self.double_edges.get(&(i, j)).map(|idx| *idx),
+            ColumnType::Triangle(i, j, k) => self.triangles.get(&(i, j, k)).map(|idx| *idx),
+            ColumnType::LongSquare(s, mid, t) => {
+                if *self.bases.get(&(s, t))? != mid.0 {
+                    return None;
+                }
+                self.long_squares.get(&(s, mid.1, t)).map(|idx| *idx)
+            }
+            ColumnType::DoubleSquare(i, mid) => {
+                if *self.loop_bases.get(&i)? != mid.0 {
+                    return None;
+                }
+                self.double_squares.get(&(i, mid.1)).map(|idx| *idx)
+            }
+        }
+    }
+}
+
+fn build_index(codomain_cells: &[GrpphatiRsColumn], regular: bool) -> CodomainIndex {
+    let index = CodomainIndex {
+        regular,
+        ..Default::default()
+    };
     codomain_cells
         .iter()
         .enumerate()
@@ -165,6 +565,190 @@ fn build_index(codomain_cells: &Vec<GrpphatiRsColumn>) -> CodomainIndex {
                 index.bases.insert((s, t), mid.0);
                 index.long_squares.insert((s, mid.1, t), idx);
             }
+            crate::columns::ColumnType::DoubleSquare(i, mid) => {
+                index.loop_bases.insert(i, mid.0);
+                index.double_squares.insert((i, mid.1), idx);
+            }
         });
     index
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(col_types: Vec<ColumnType>) -> Vec<GrpphatiRsColumn> {
+        col_types
+            .into_iter()
+            .map(|col_type| GrpphatiRsColumn {
+                col_type,
+                entrance_time: Some(0.0),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn collapsed_loop_at_its_only_double_edge() {
+        let codomain = cells(vec![
+            ColumnType::Node(0),
+            ColumnType::Node(1),
+            ColumnType::Edge(0, 1),
+            ColumnType::Edge(1, 0),
+        ]);
+        let index = build_index(&codomain, false);
+        let vertex_map = NodeMapping::from([(0, 0), (1, 1), (2, 1)]);
+        let cell = ColumnType::DoubleSquare(0, (1, 2));
+        let image = compute_cell_image(&index, &vertex_map, cell).unwrap();
+        assert!(image.is_empty());
+    }
+
+    #[test]
+    fn missing_loop_is_not_zero() {
+        let codomain = cells(vec![
+            ColumnType::Node(0),
+            ColumnType::Node(1),
+            ColumnType::Node(2),
+            ColumnType::Edge(0, 1),
+            ColumnType::Edge(1, 0),
+            ColumnType::Edge(0, 2),
+        ]);
+        let index = build_index(&codomain, false);
+        let vertex_map = NodeMapping::from([(0, 0), (1, 1), (2, 2)]);
+        let cell = ColumnType::DoubleSquare(0, (1, 2));
+        let err = compute_cell_image(&index, &vertex_map, cell).unwrap_err();
+        assert!(matches!(
+            err,
+            ChainMapError::MissingImage {
+                image: ColumnType::DoubleEdge(0, 2),
+                ..
+            }
+        ));
+    }
+
+    // A long square whose endpoints are never joined, so there is no base triangle
+    #[test]
+    fn long_square_without_base_triangle() {
+        let codomain = cells(vec![
+            ColumnType::Node(0),
+            ColumnType::Node(1),
+            ColumnType::Node(2),
+            ColumnType::Node(3),
+            ColumnType::Edge(0, 1),
+            ColumnType::Edge(1, 2),
+            ColumnType::Edge(0, 3),
+            ColumnType::Edge(3, 2),
+            ColumnType::LongSquare(0, (1, 3), 2),
+        ]);
+        let vertex_map = NodeMapping::from([(0, 0), (1, 1), (2, 2), (3, 3)]);
+        verify_chain_map(&codomain, &codomain, &vertex_map).unwrap();
+        let map = compute_map(&codomain, &codomain, vertex_map).unwrap();
+        assert_eq!(map, (0..codomain.len()).map(|idx| vec![idx]).collect_vec());
+    }
+
+    fn timed_cells(cells: &[(ColumnType, FiltrationTime)]) -> Vec<GrpphatiRsColumn> {
+        cells
+            .iter()
+            .map(|&(col_type, time)| GrpphatiRsColumn {
+                col_type,
+                entrance_time: Some(time),
+            })
+            .collect()
+    }
+
+    // The triangle 0 → 1 → 2 with 0 → 2, filled in when 0 → 2 enters, or later
+    fn triangle(fill_time: FiltrationTime) -> Vec<GrpphatiRsColumn> {
+        timed_cells(&[
+            (ColumnType::Node(0), 0.0),
+            (ColumnType::Node(1), 0.0),
+            (ColumnType::Node(2), 0.0),
+            (ColumnType::Edge(0, 1), 1.0),
+            (ColumnType::Edge(1, 2), 1.0),
+            (ColumnType::Edge(0, 2), 2.0),
+            (ColumnType::Triangle(0, 1, 2), fill_time),
+        ])
+    }
+
+    fn identity(n: NodeIndex) -> NodeMapping {
+        (0..n).map(|node| (node, node)).collect()
+    }
+
+    #[test]
+    fn identity_is_a_chain_map() {
+        let cells = triangle(2.0);
+        verify_chain_map(&cells, &cells, &identity(3)).unwrap();
+        let map = compute_map(&cells, &cells, identity(3)).unwrap();
+        assert_eq!(map, (0..cells.len()).map(|idx| vec![idx]).collect_vec());
+    }
+
+    #[test]
+    fn reports_missing_vertices() {
+        let cells = triangle(2.0);
+        let vertex_map = NodeMapping::from([(0, 0), (1, 1)]);
+        let errors = verify_chain_map(&cells, &cells, &vertex_map).unwrap_err();
+        assert!(errors.contains(&ChainMapError::MissingVertex {
+            cell: ColumnType::Node(2),
+            vertex: 2
+        }));
+        let errors = compute_map(&cells, &cells, vertex_map).unwrap_err();
+        assert_eq!(errors.len(), 4);
+        assert!(errors
+            .iter()
+            .all(|err| matches!(err, ChainMapError::MissingVertex { vertex: 2, .. })));
+    }
+
+    #[test]
+    fn reports_images_entering_later() {
+        let errors = verify_chain_map(&triangle(2.0), &triangle(3.0), &identity(3)).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![ChainMapError::FiltrationViolation {
+                cell: ColumnType::Triangle(0, 1, 2),
+                cell_time: 2.0,
+                image: ColumnType::Triangle(0, 1, 2),
+                image_time: 3.0,
+            }]
+        );
+    }
+
+    // The codomain has a long square based at 0 → 1 → 2 but not the triangle itself,
+    // so the triangle is sent to zero although its boundary is not
+    #[test]
+    fn reports_maps_not_commuting_with_the_boundary() {
+        let codomain = timed_cells(&[
+            (ColumnType::Node(0), 0.0),
+            (ColumnType::Node(1), 0.0),
+            (ColumnType::Node(2), 0.0),
+            (ColumnType::Node(3), 0.0),
+            (ColumnType::Edge(0, 1), 1.0),
+            (ColumnType::Edge(1, 2), 1.0),
+            (ColumnType::Edge(0, 2), 2.0),
+            (ColumnType::Edge(0, 3), 1.0),
+            (ColumnType::Edge(3, 2), 1.0),
+            (ColumnType::LongSquare(0, (1, 3), 2), 1.0),
+        ]);
+        let errors = verify_chain_map(&triangle(2.0), &codomain, &identity(4)).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![ChainMapError::NonCommuting {
+                cell: ColumnType::Triangle(0, 1, 2),
+                boundary_of_image: vec![],
+                image_of_boundary: vec![
+                    ColumnType::Edge(0, 1),
+                    ColumnType::Edge(1, 2),
+                    ColumnType::Edge(0, 2)
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn collapsing_an_edge_is_an_error_without_regularity() {
+        let cells = triangle(2.0);
+        let vertex_map = NodeMapping::from([(0, 0), (1, 0), (2, 2)]);
+        assert!(compute_map(&cells, &cells, vertex_map.clone()).is_ok());
+        let errors = compute_nrph_map(&cells, &cells, vertex_map).unwrap_err();
+        assert!(errors.contains(&ChainMapError::CollapsedEdge {
+            cell: ColumnType::Edge(0, 1)
+        }));
+    }
+}
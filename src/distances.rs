@@ -0,0 +1,388 @@
+use std::str::FromStr;
+
+use pyo3::{exceptions::PyValueError, prelude::*};
+use rayon::prelude::*;
+
+use crate::{
+    invariants::{bars_in_dimension, to_numpy, Bar},
+    vectorizations::IndexedDiagram,
+    FiltrationTime,
+};
+
+/// Distances between persistence diagrams, with the L∞ distance between points.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DiagramDistance {
+    Bottleneck,
+    /// The p-Wasserstein distance, for p >= 1.
+    Wasserstein(f64),
+}
+
+impl DiagramDistance {
+    pub fn new(metric: &str, p: f64) -> Result<Self, String> {
+        let distance: Self = metric.parse()?;
+        if let DiagramDistance::Wasserstein(_) = distance {
+            if !(1.0..FiltrationTime::INFINITY).contains(&p) {
+                return Err(format!("p must be finite and at least 1, not {p}"));
+            }
+            return Ok(DiagramDistance::Wasserstein(p));
+        }
+        Ok(distance)
+    }
+}
+
+// Wasserstein distances are parsed with p = 2, see `DiagramDistance::new` for other values
+impl FromStr for DiagramDistance {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "bottleneck" => Ok(DiagramDistance::Bottleneck),
+            "wasserstein" => Ok(DiagramDistance::Wasserstein(2.0)),
+            _ => Err(format!(
+                "Unknown metric {name}, expected bottleneck or wasserstein"
+            )),
+        }
+    }
+}
+
+/// The bottleneck distance between the bars of two diagrams in `dimension`,
+/// each given with the cells it indexes into, as returned by `grpph_pipeline`.
+/// Essential bars are only matched with each other, in order of birth,
+/// so the distance is infinite if the diagrams have different numbers of them.
+#[pyfunction]
+#[pyo3(name = "bottleneck_distance")]
+#[pyo3(signature = (diagram_a, diagram_b, dimension = 1))]
+pub fn bottleneck_distance_py(
+    diagram_a: IndexedDiagram,
+    diagram_b: IndexedDiagram,
+    dimension: usize,
+) -> PyResult<f64> {
+    let a = indexed_bars(&diagram_a, dimension)?;
+    let b = indexed_bars(&diagram_b, dimension)?;
+    Ok(diagram_distance(&a, &b, DiagramDistance::Bottleneck))
+}
+
+/// The p-Wasserstein distance between the bars of two diagrams in `dimension`.
+/// See `bottleneck_distance` for the treatment of essential bars.
+#[pyfunction]
+#[pyo3(name = "wasserstein_distance")]
+#[pyo3(signature = (diagram_a, diagram_b, p = 2.0, dimension = 1))]
+pub fn wasserstein_distance_py(
+    diagram_a: IndexedDiagram,
+    diagram_b: IndexedDiagram,
+    p: f64,
+    dimension: usize,
+) -> PyResult<f64> {
+    let distance = DiagramDistance::new("wasserstein", p).map_err(PyValueError::new_err)?;
+    let a = indexed_bars(&diagram_a, dimension)?;
+    let b = indexed_bars(&diagram_b, dimension)?;
+    Ok(diagram_distance(&a, &b, distance))
+}
+
+/// The matrix of distances between each pair of diagrams, computed in parallel, as a NumPy array.
+/// `metric` is bottleneck or wasserstein, in which case `p` is used.
+/// See `bottleneck` for the memory used by each pair.
+#[pyfunction]
+#[pyo3(name = "pairwise_distances")]
+#[pyo3(signature = (diagrams, metric = "bottleneck", p = 2.0, dimension = 1))]
+pub fn pairwise_distances_py(
+    py: Python<'_>,
+    diagrams: Vec<IndexedDiagram>,
+    metric: &str,
+    p: f64,
+    dimension: usize,
+) -> PyResult<PyObject> {
+    let distance = DiagramDistance::new(metric, p).map_err(PyValueError::new_err)?;
+    let matrix = py.allow_threads(|| {
+        let bars = diagrams
+            .par_iter()
+            .map(|diagram| bars_in_dimension(&diagram.0, &diagram.1, dimension))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok::<_, String>(pairwise_distances(&bars, distance))
+    });
+    to_numpy(py, matrix.map_err(PyValueError::new_err)?, "float64")
+}
+
+fn indexed_bars(diagram: &IndexedDiagram, dimension: usize) -> PyResult<Vec<Bar>> {
+    bars_in_dimension(&diagram.0, &diagram.1, dimension).map_err(PyValueError::new_err)
+}
+
+/// Symmetric matrix of the distances between each pair of diagrams, with the pairs computed in parallel.
+pub fn pairwise_distances(diagrams: &[Vec<Bar>], distance: DiagramDistance) -> Vec<Vec<f64>> {
+    let n = diagrams.len();
+    let pairs: Vec<(usize, usize)> = (0..n)
+        .flat_map(|i| ((i + 1)..n).map(move |j| (i, j)))
+        .collect();
+    let values: Vec<f64> = pairs
+        .par_iter()
+        .map(|&(i, j)| diagram_distance(&diagrams[i], &diagrams[j], distance))
+        .collect();
+    let mut matrix = vec![vec![0.0; n]; n];
+    for (&(i, j), value) in pairs.iter().zip(values) {
+        matrix[i][j] = value;
+        matrix[j][i] = value;
+    }
+    matrix
+}
+
+/// The distance between two diagrams, given as bars with infinite deaths for essential features.
+/// Bars of length zero lie on the diagonal and are ignored.
+pub fn diagram_distance(a: &[Bar], b: &[Bar], distance: DiagramDistance) -> f64 {
+    let (finite_a, essential_a) = split_bars(a);
+    let (finite_b, essential_b) = split_bars(b);
+    if essential_a.len() != essential_b.len() {
+        return FiltrationTime::INFINITY;
+    }
+    let essential_gaps = essential_a
+        .iter()
+        .zip(essential_b.iter())
+        .map(|(birth_a, birth_b)| (birth_a - birth_b).abs());
+    match distance {
+        DiagramDistance::Bottleneck => {
+            let finite = bottleneck(&finite_a, &finite_b);
+            essential_gaps.fold(finite, f64::max)
+        }
+        DiagramDistance::Wasserstein(p) => {
+            let finite = wasserstein_cost(&finite_a, &finite_b, p);
+            let essential: f64 = essential_gaps.map(|gap| gap.powf(p)).sum();
+            (finite + essential).powf(1.0 / p)
+        }
+    }
+}
+
+// Finite bars of positive length, and the sorted births of essential bars
+fn split_bars(bars: &[Bar]) -> (Vec<Bar>, Vec<FiltrationTime>) {
+    let finite = bars
+        .iter()
+        .copied()
+        .filter(|&(birth, death)| death.is_finite() && birth < death)
+        .collect();
+    let mut essential: Vec<_> = bars
+        .iter()
+        .filter(|(_, death)| death.is_infinite())
+        .map(|&(birth, _)| birth)
+        .collect();
+    essential.sort_by(|x, y| x.partial_cmp(y).expect("Births should not be NaN"));
+    (finite, essential)
+}
+
+fn point_distance(x: Bar, y: Bar) -> f64 {
+    (x.0 - y.0).abs().max((x.1 - y.1).abs())
+}
+
+// L∞ distance to the nearest point of the diagonal
+fn diagonal_distance(x: Bar) -> f64 {
+    (x.1 - x.0) / 2.0
+}
+
+/// The minimum over matchings of the sum of the p-th powers of the distances,
+/// where each point of `a` (rows) or `b` (columns) may instead be matched with the diagonal.
+/// Solved exactly with the Hungarian algorithm on the (n + m) × (n + m) matrix
+/// in which the last m rows and n columns stand for the diagonal.
+fn wasserstein_cost(a: &[Bar], b: &[Bar], p: f64) -> f64 {
+    let (n, m) = (a.len(), b.len());
+    let size = n + m;
+    if size == 0 {
+        return 0.0;
+    }
+    let cost = |i: usize, j: usize| -> f64 {
+        match (i < n, j < m) {
+            (true, true) => point_distance(a[i], b[j]).powf(p),
+            (true, false) => diagonal_distance(a[i]).powf(p),
+            (false, true) => diagonal_distance(b[j]).powf(p),
+            (false, false) => 0.0,
+        }
+    };
+    hungarian(size, cost)
+}
+
+// Minimum cost perfect matching on a square matrix, via shortest augmenting paths with potentials.
+// Arrays are 1-indexed, with index 0 standing for the unmatched state.
+fn hungarian(size: usize, cost: impl Fn(usize, usize) -> f64) -> f64 {
+    let mut u = vec![0.0; size + 1];
+    let mut v = vec![0.0; size + 1];
+    // row_of[j] is the row matched to column j
+    let mut row_of = vec![0; size + 1];
+    let mut way = vec![0; size + 1];
+    for i in 1..=size {
+        row_of[0] = i;
+        let mut j0 = 0;
+        let mut min_slack = vec![f64::INFINITY; size + 1];
+        let mut used = vec![false; size + 1];
+        loop {
+            used[j0] = true;
+            let i0 = row_of[j0];
+            let mut delta = f64::INFINITY;
+            let mut j1 = 0;
+            for j in 1..=size {
+                if used[j] {
+                    continue;
+                }
+                let slack = cost(i0 - 1, j - 1) - u[i0] - v[j];
+                if slack < min_slack[j] {
+                    min_slack[j] = slack;
+                    way[j] = j0;
+                }
+                if min_slack[j] < delta {
+                    delta = min_slack[j];
+                    j1 = j;
+                }
+            }
+            for j in 0..=size {
+                if used[j] {
+                    u[row_of[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min_slack[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if row_of[j0] == 0 {
+                break;
+            }
+        }
+        // Augment along the path back to the root
+        while j0 != 0 {
+            let j1 = way[j0];
+            row_of[j0] = row_of[j1];
+            j0 = j1;
+        }
+    }
+    (1..=size).map(|j| cost(row_of[j] - 1, j - 1)).sum()
+}
+
+/// The bottleneck distance between finite bars: the least ε for which there is a perfect matching,
+/// with the diagonal, using only pairs within ε.
+/// Found by binary search over the candidate values of ε, checking each with augmenting paths.
+/// The n·m pairwise distances are all held as candidates, so memory grows with the product of the sizes.
+fn bottleneck(a: &[Bar], b: &[Bar]) -> f64 {
+    let mut candidates: Vec<f64> = a
+        .iter()
+        .flat_map(|&x| b.iter().map(move |&y| point_distance(x, y)))
+        .chain(a.iter().chain(b.iter()).map(|&x| diagonal_distance(x)))
+        .chain([0.0])
+        .collect();
+    candidates.sort_by(|x, y| x.partial_cmp(y).expect("Distances should not be NaN"));
+    candidates.dedup();
+    // The largest candidate always admits a matching, since every point can go to the diagonal
+    let (mut low, mut high) = (0, candidates.len() - 1);
+    while low < high {
+        let mid = (low + high) / 2;
+        if has_perfect_matching(a, b, candidates[mid]) {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    candidates[low]
+}
+
+// Rows are the points of a then a diagonal copy of each point of b,
+// columns are the points of b then a diagonal copy of each point of a
+fn has_perfect_matching(a: &[Bar], b: &[Bar], epsilon: f64) -> bool {
+    let (n, m) = (a.len(), b.len());
+    let size = n + m;
+    let adjacent = |i: usize, j: usize| match (i < n, j < m) {
+        (true, true) => point_distance(a[i], b[j]) <= epsilon,
+        (true, false) => j - m == i && diagonal_distance(a[i]) <= epsilon,
+        (false, true) => i - n == j && diagonal_distance(b[j]) <= epsilon,
+        (false, false) => true,
+    };
+    let mut row_of: Vec<Option<usize>> = vec![None; size];
+    for i in 0..size {
+        let mut visited = vec![false; size];
+        if !augment(i, &adjacent, &mut row_of, &mut visited) {
+            return false;
+        }
+    }
+    true
+}
+
+// Kuhn's algorithm: tries to find an augmenting path from row i
+fn augment(
+    i: usize,
+    adjacent: &impl Fn(usize, usize) -> bool,
+    row_of: &mut [Option<usize>],
+    visited: &mut [bool],
+) -> bool {
+    for j in 0..row_of.len() {
+        if visited[j] || !adjacent(i, j) {
+            continue;
+        }
+        visited[j] = true;
+        let free = match row_of[j] {
+            None => true,
+            Some(other) => augment(other, adjacent, row_of, visited),
+        };
+        if free {
+            row_of[j] = Some(i);
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INF: FiltrationTime = FiltrationTime::INFINITY;
+
+    #[test]
+    fn hungarian_finds_the_cheapest_matching() {
+        let costs = [[4.0, 1.0, 3.0], [2.0, 0.0, 5.0], [3.0, 2.0, 2.0]];
+        assert_eq!(hungarian(3, |i, j| costs[i][j]), 5.0);
+        assert_eq!(hungarian(1, |_, _| 7.0), 7.0);
+    }
+
+    // (0, 4) and (0, 3) are 1 apart, and 2 and 1.5 from the diagonal
+    #[test]
+    fn matches_points_or_the_diagonal() {
+        let (a, b) = ([(0.0, 4.0)], [(0.0, 3.0)]);
+        assert!(has_perfect_matching(&a, &b, 1.0));
+        assert!(!has_perfect_matching(&a, &b, 0.9));
+        assert!(has_perfect_matching(&a, &[], 2.0));
+        assert!(!has_perfect_matching(&a, &[], 1.5));
+        assert_eq!(diagram_distance(&a, &b, DiagramDistance::Bottleneck), 1.0);
+        assert_eq!(diagram_distance(&a, &[], DiagramDistance::Bottleneck), 2.0);
+        assert_eq!(
+            diagram_distance(&a, &b, DiagramDistance::Wasserstein(2.0)),
+            1.0
+        );
+        assert_eq!(
+            diagram_distance(&a, &[], DiagramDistance::Wasserstein(1.0)),
+            2.0
+        );
+    }
+
+    // The essential bars are 0.5 apart and (1, 2) is 0.5 from the diagonal
+    #[test]
+    fn matches_essential_bars_in_order_of_birth() {
+        let a = [(0.0, INF), (1.0, 2.0), (3.0, INF)];
+        let b = [(3.0, INF), (0.5, INF), (1.0, 1.0)];
+        assert_eq!(diagram_distance(&a, &b, DiagramDistance::Bottleneck), 0.5);
+        assert_eq!(
+            diagram_distance(&a, &b, DiagramDistance::Wasserstein(1.0)),
+            1.0
+        );
+        assert_eq!(
+            diagram_distance(&a, &b[1..], DiagramDistance::Bottleneck),
+            INF
+        );
+    }
+
+    #[test]
+    fn pairwise_distances_are_symmetric() {
+        let diagrams = vec![vec![(0.0, 4.0)], vec![(0.0, 3.0)], vec![]];
+        assert_eq!(
+            pairwise_distances(&diagrams, DiagramDistance::Bottleneck),
+            vec![
+                vec![0.0, 1.0, 2.0],
+                vec![1.0, 0.0, 1.5],
+                vec![2.0, 1.5, 0.0]
+            ]
+        );
+        assert!(DiagramDistance::new("wasserstein", 0.5).is_err());
+        assert!(DiagramDistance::new("l2", 2.0).is_err());
+    }
+}
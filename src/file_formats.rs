@@ -0,0 +1,318 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    str::FromStr,
+};
+
+use pyo3::{
+    exceptions::{PyIOError, PyValueError},
+    prelude::*,
+};
+
+use crate::FiltrationTime;
+
+/// A boundary matrix as `(dimension, boundary)` pairs, as produced by the sparsifiers.
+pub type BoundaryMatrix = Vec<(usize, Vec<usize>)>;
+
+// Identifies DIPHA files, followed by the file type
+const DIPHA_MAGIC: i64 = 8067171840;
+const DIPHA_WEIGHTED_BOUNDARY_MATRIX: i64 = 0;
+// The is_boolean flag, set for matrices over Z/2
+const DIPHA_BOOLEAN: i64 = 1;
+
+/// File formats understood by external reducers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MatrixFormat {
+    /// One column per line, `dim row row ...`, with `#` comment lines.
+    PhatAscii,
+    /// Little-endian int64s: the number of columns, then `dim`, `#rows`, rows for each column.
+    PhatBinary,
+    /// DIPHA's weighted boundary matrix, which also stores entrance times.
+    Dipha,
+}
+
+impl FromStr for MatrixFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "phat_ascii" => Ok(MatrixFormat::PhatAscii),
+            "phat_binary" => Ok(MatrixFormat::PhatBinary),
+            "dipha" => Ok(MatrixFormat::Dipha),
+            _ => Err(format!(
+                "Unknown format {name}, expected one of phat_ascii, phat_binary or dipha"
+            )),
+        }
+    }
+}
+
+/// Writes the `(dimension, boundary)` output of a sparsifier to `path`.
+/// PHAT formats have no notion of entrance times, so these are only written for DIPHA;
+/// if none are given, the column index is used instead.
+#[pyfunction]
+#[pyo3(name = "write_boundary_matrix")]
+#[pyo3(signature = (path, matrix, entrance_times = None, format = "phat_ascii"))]
+pub fn write_boundary_matrix_py(
+    path: &str,
+    matrix: BoundaryMatrix,
+    entrance_times: Option<Vec<FiltrationTime>>,
+    format: &str,
+) -> PyResult<()> {
+    let format: MatrixFormat = format.parse().map_err(PyValueError::new_err)?;
+    if entrance_times
+        .as_ref()
+        .is_some_and(|times| times.len() != matrix.len())
+    {
+        return Err(PyValueError::new_err(
+            "Must provide one entrance time per column",
+        ));
+    }
+    let file = File::create(path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+    let mut writer = BufWriter::new(file);
+    write_boundary_matrix(&mut writer, &matrix, entrance_times.as_deref(), format)
+        .and_then(|_| writer.flush())
+        .map_err(|e| PyIOError::new_err(e.to_string()))
+}
+
+/// Reads a boundary matrix from `path`.
+/// Entrance times are only returned for DIPHA files.
+#[pyfunction]
+#[pyo3(name = "read_boundary_matrix")]
+#[pyo3(signature = (path, format = "phat_ascii"))]
+pub fn read_boundary_matrix_py(
+    path: &str,
+    format: &str,
+) -> PyResult<(BoundaryMatrix, Option<Vec<FiltrationTime>>)> {
+    let format: MatrixFormat = format.parse().map_err(PyValueError::new_err)?;
+    let file = File::open(path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+    read_boundary_matrix(BufReader::new(file), format).map_err(|e| match e.kind() {
+        io::ErrorKind::InvalidData => PyValueError::new_err(e.to_string()),
+        _ => PyIOError::new_err(e.to_string()),
+    })
+}
+
+pub fn write_boundary_matrix(
+    mut writer: impl Write,
+    matrix: &[(usize, Vec<usize>)],
+    entrance_times: Option<&[FiltrationTime]>,
+    format: MatrixFormat,
+) -> io::Result<()> {
+    match format {
+        MatrixFormat::PhatAscii => {
+            for (dim, boundary) in matrix {
+                write!(writer, "{dim}")?;
+                for row in boundary {
+                    write!(writer, " {row}")?;
+                }
+                writeln!(writer)?;
+            }
+        }
+        MatrixFormat::PhatBinary => {
+            write_i64(&mut writer, matrix.len())?;
+            for (dim, boundary) in matrix {
+                write_i64(&mut writer, *dim)?;
+                write_i64(&mut writer, boundary.len())?;
+                for &row in boundary {
+                    write_i64(&mut writer, row)?;
+                }
+            }
+        }
+        MatrixFormat::Dipha => {
+            let max_dim = matrix.iter().map(|(dim, _)| *dim).max().unwrap_or(0);
+            for value in [DIPHA_MAGIC, DIPHA_WEIGHTED_BOUNDARY_MATRIX, DIPHA_BOOLEAN] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            write_i64(&mut writer, matrix.len())?;
+            write_i64(&mut writer, max_dim)?;
+            for (dim, _) in matrix {
+                write_i64(&mut writer, *dim)?;
+            }
+            for idx in 0..matrix.len() {
+                let time = entrance_times.map_or(idx as FiltrationTime, |times| times[idx]);
+                writer.write_all(&time.to_le_bytes())?;
+            }
+            // Offset of each column into the list of entries
+            let mut offset = 0;
+            for (_, boundary) in matrix {
+                write_i64(&mut writer, offset)?;
+                offset += boundary.len();
+            }
+            write_i64(&mut writer, offset)?;
+            for (_, boundary) in matrix {
+                for &row in boundary {
+                    write_i64(&mut writer, row)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn read_boundary_matrix(
+    mut reader: impl BufRead,
+    format: MatrixFormat,
+) -> io::Result<(BoundaryMatrix, Option<Vec<FiltrationTime>>)> {
+    match format {
+        MatrixFormat::PhatAscii => {
+            let mut matrix = vec![];
+            for line in reader.lines() {
+                let line = line?;
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let mut entries = line.split_whitespace().map(|entry| {
+                    entry
+                        .parse::<usize>()
+                        .map_err(|_| invalid_data(format!("Invalid entry {entry}")))
+                });
+                let dim = entries.next().unwrap()?;
+                let boundary = entries.collect::<io::Result<_>>()?;
+                matrix.push((dim, boundary));
+            }
+            Ok((matrix, None))
+        }
+        MatrixFormat::PhatBinary => {
+            let n_cols = read_usize(&mut reader)?;
+            // Not preallocated, since a corrupt header could give any number of columns
+            let mut matrix = vec![];
+            for _ in 0..n_cols {
+                let dim = read_usize(&mut reader)?;
+                let n_rows = read_usize(&mut reader)?;
+                let boundary = (0..n_rows)
+                    .map(|_| read_usize(&mut reader))
+                    .collect::<io::Result<_>>()?;
+                matrix.push((dim, boundary));
+            }
+            Ok((matrix, None))
+        }
+        MatrixFormat::Dipha => {
+            if read_i64(&mut reader)? != DIPHA_MAGIC {
+                return Err(invalid_data("Not a DIPHA file".to_string()));
+            }
+            if read_i64(&mut reader)? != DIPHA_WEIGHTED_BOUNDARY_MATRIX {
+                return Err(invalid_data(
+                    "DIPHA file is not a weighted boundary matrix".to_string(),
+                ));
+            }
+            if read_i64(&mut reader)? != DIPHA_BOOLEAN {
+                return Err(invalid_data(
+                    "Only boolean DIPHA boundary matrices are supported".to_string(),
+                ));
+            }
+            let n_cols = read_usize(&mut reader)?;
+            let _max_dim = read_usize(&mut reader)?;
+            let dims = (0..n_cols)
+                .map(|_| read_usize(&mut reader))
+                .collect::<io::Result<Vec<_>>>()?;
+            let times = (0..n_cols)
+                .map(|_| {
+                    let mut bytes = [0; 8];
+                    reader.read_exact(&mut bytes)?;
+                    Ok(FiltrationTime::from_le_bytes(bytes))
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            let offsets = (0..n_cols)
+                .map(|_| read_usize(&mut reader))
+                .collect::<io::Result<Vec<_>>>()?;
+            let n_entries = read_usize(&mut reader)?;
+            let entries = (0..n_entries)
+                .map(|_| read_usize(&mut reader))
+                .collect::<io::Result<Vec<_>>>()?;
+            let ends = offsets.iter().skip(1).copied().chain([n_entries]);
+            let mut matrix = Vec::with_capacity(dims.len());
+            for ((dim, &start), end) in dims.into_iter().zip(offsets.iter()).zip(ends) {
+                if start > end || end > n_entries {
+                    return Err(invalid_data("Invalid DIPHA column offsets".to_string()));
+                }
+                matrix.push((dim, entries[start..end].to_vec()));
+            }
+            Ok((matrix, Some(times)))
+        }
+    }
+}
+
+pub(crate) fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_i64(writer: &mut impl Write, value: usize) -> io::Result<()> {
+    writer.write_all(&(value as i64).to_le_bytes())
+}
+
+fn read_i64(reader: &mut impl Read) -> io::Result<i64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(i64::from_le_bytes(bytes))
+}
+
+fn read_usize(reader: &mut impl Read) -> io::Result<usize> {
+    let value = read_i64(reader)?;
+    usize::try_from(value).map_err(|_| invalid_data(format!("Unexpected negative value {value}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix() -> BoundaryMatrix {
+        vec![
+            (0, vec![]),
+            (0, vec![]),
+            (1, vec![0, 1]),
+            (0, vec![]),
+            (1, vec![1, 3]),
+            (1, vec![0, 3]),
+            (2, vec![2, 4, 5]),
+        ]
+    }
+
+    fn round_trip(
+        format: MatrixFormat,
+        times: Option<&[FiltrationTime]>,
+    ) -> (BoundaryMatrix, Option<Vec<FiltrationTime>>) {
+        let mut bytes = vec![];
+        write_boundary_matrix(&mut bytes, &matrix(), times, format).unwrap();
+        read_boundary_matrix(bytes.as_slice(), format).unwrap()
+    }
+
+    #[test]
+    fn phat_round_trips() {
+        for format in [MatrixFormat::PhatAscii, MatrixFormat::PhatBinary] {
+            assert_eq!(round_trip(format, None), (matrix(), None));
+        }
+    }
+
+    #[test]
+    fn dipha_round_trips() {
+        let times = [0.0, 0.0, 1.0, 1.0, 2.0, 2.5, 3.0];
+        let (read, read_times) = round_trip(MatrixFormat::Dipha, Some(&times));
+        assert_eq!(read, matrix());
+        assert_eq!(read_times, Some(times.to_vec()));
+        let (_, read_times) = round_trip(MatrixFormat::Dipha, None);
+        assert_eq!(read_times, Some(vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0]));
+    }
+
+    // Magic number, weighted boundary matrix, is_boolean, number of columns and dimension
+    #[test]
+    fn dipha_header() {
+        let mut bytes = vec![];
+        write_boundary_matrix(&mut bytes, &matrix(), None, MatrixFormat::Dipha).unwrap();
+        let header: Vec<i64> = bytes[..40]
+            .chunks(8)
+            .map(|word| i64::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        assert_eq!(header, vec![8067171840, 0, 1, 7, 2]);
+    }
+
+    #[test]
+    fn corrupt_column_count_is_an_error() {
+        let mut bytes = vec![];
+        write_i64(&mut bytes, 1 << 60).unwrap();
+        assert!(read_boundary_matrix(bytes.as_slice(), MatrixFormat::PhatBinary).is_err());
+        let mut bytes = vec![];
+        write_boundary_matrix(&mut bytes, &matrix(), None, MatrixFormat::Dipha).unwrap();
+        bytes[24..32].copy_from_slice(&(1i64 << 60).to_le_bytes());
+        assert!(read_boundary_matrix(bytes.as_slice(), MatrixFormat::Dipha).is_err());
+    }
+}
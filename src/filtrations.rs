@@ -0,0 +1,261 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    fmt,
+    str::FromStr,
+};
+
+use pyo3::{exceptions::PyValueError, prelude::*};
+use rayon::prelude::*;
+
+use crate::{
+    graph::{report_self_loops, WeightedDigraph},
+    homology::EdgeMap,
+    print_progress, FiltrationTime, NodeIndex,
+};
+
+/// Filtrations of the complete digraph on the nodes of a weighted digraph.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FiltrationKind {
+    /// i → j enters at the length of the shortest path from i to j.
+    ShortestPath,
+    /// Only the original edges, entering at their weights.
+    Weight,
+    /// i → j enters at the least number of hops needed to reach j from i.
+    HopCount,
+    /// i → j enters at the minimum over paths from i to j of the largest edge weight on the path.
+    Minimax,
+}
+
+impl FromStr for FiltrationKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "shortest_path" => Ok(FiltrationKind::ShortestPath),
+            "weight" => Ok(FiltrationKind::Weight),
+            "hop_count" => Ok(FiltrationKind::HopCount),
+            "minimax" => Ok(FiltrationKind::Minimax),
+            _ => Err(format!(
+                "Unknown filtration {name}, expected one of shortest_path, weight, hop_count or minimax"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FiltrationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FiltrationKind::ShortestPath => write!(f, "shortest_path"),
+            FiltrationKind::Weight => write!(f, "weight"),
+            FiltrationKind::HopCount => write!(f, "hop_count"),
+            FiltrationKind::Minimax => write!(f, "minimax"),
+        }
+    }
+}
+
+/// Builds the named filtration of the digraph with the given nodes and weighted edges,
+/// in the form consumed by `get_rph_two_cells`.
+#[pyfunction]
+#[pyo3(name = "build_filtration")]
+pub fn build_filtration_py(
+    nodes: Vec<NodeIndex>,
+    edges: Vec<(NodeIndex, NodeIndex, FiltrationTime)>,
+    filtration: &str,
+) -> PyResult<EdgeMap> {
+    let kind: FiltrationKind = filtration.parse().map_err(PyValueError::new_err)?;
+    let (graph, skipped) =
+        WeightedDigraph::new_counting_self_loops(nodes, edges).map_err(PyValueError::new_err)?;
+    report_self_loops(skipped, &print_progress);
+    Ok(build_filtration(&graph, kind))
+}
+
+/// Every node of `graph` appears as a key of the returned map, even if it has no out-edges.
+/// Pairs (i, j) where j is not reachable from i are omitted.
+pub fn build_filtration(graph: &WeightedDigraph, kind: FiltrationKind) -> EdgeMap {
+    let neighbours = graph.out_neighbours();
+    match kind {
+        FiltrationKind::Weight => neighbours
+            .into_iter()
+            .map(|(source, targets)| (source, targets.into_iter().collect()))
+            .collect(),
+        FiltrationKind::ShortestPath => {
+            all_sources(graph, |source| dijkstra(&neighbours, source, |a, b| a + b))
+        }
+        FiltrationKind::Minimax => all_sources(graph, |source| {
+            dijkstra(&neighbours, source, FiltrationTime::max)
+        }),
+        FiltrationKind::HopCount => all_sources(graph, |source| hop_counts(&neighbours, source)),
+    }
+}
+
+fn all_sources(
+    graph: &WeightedDigraph,
+    distances_from: impl Fn(NodeIndex) -> HashMap<NodeIndex, FiltrationTime> + Sync,
+) -> EdgeMap {
+    graph
+        .nodes
+        .par_iter()
+        .map(|&source| {
+            let mut distances = distances_from(source);
+            distances.remove(&source);
+            (source, distances)
+        })
+        .collect()
+}
+
+// Min-heap entry, ordered by distance
+#[derive(PartialEq)]
+struct HeapEntry(FiltrationTime, NodeIndex);
+
+impl Eq for HeapEntry {}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .0
+            .partial_cmp(&self.0)
+            .expect("Distances should not be NaN")
+            .then_with(|| other.1.cmp(&self.1))
+    }
+}
+
+// Dijkstra's algorithm, where the length of a path is obtained by folding its weights with `extend`.
+// Valid for any `extend` which is monotone in both arguments, e.g. sum or max.
+fn dijkstra(
+    neighbours: &HashMap<NodeIndex, Vec<(NodeIndex, FiltrationTime)>>,
+    source: NodeIndex,
+    extend: impl Fn(FiltrationTime, FiltrationTime) -> FiltrationTime,
+) -> HashMap<NodeIndex, FiltrationTime> {
+    let mut distances: HashMap<NodeIndex, FiltrationTime> = HashMap::new();
+    let mut heap = BinaryHeap::from([HeapEntry(0.0, source)]);
+    while let Some(HeapEntry(distance, node)) = heap.pop() {
+        if distances.contains_key(&node) {
+            continue;
+        }
+        distances.insert(node, distance);
+        for &(target, weight) in neighbours[&node].iter() {
+            if !distances.contains_key(&target) {
+                heap.push(HeapEntry(extend(distance, weight), target));
+            }
+        }
+    }
+    distances
+}
+
+fn hop_counts(
+    neighbours: &HashMap<NodeIndex, Vec<(NodeIndex, FiltrationTime)>>,
+    source: NodeIndex,
+) -> HashMap<NodeIndex, FiltrationTime> {
+    let mut distances = HashMap::from([(source, 0.0)]);
+    let mut queue = VecDeque::from([source]);
+    while let Some(node) = queue.pop_front() {
+        let hops = distances[&node] + 1.0;
+        for &(target, _) in neighbours[&node].iter() {
+            distances.entry(target).or_insert_with(|| {
+                queue.push_back(target);
+                hops
+            });
+        }
+    }
+    distances
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0 → 1 → 2 → 3 with weights 1, 2.5 and 1, parallel edges 0 → 2 of weights 3 and 6, and the isolated node 4
+    fn graph() -> WeightedDigraph {
+        WeightedDigraph::new(
+            vec![4],
+            vec![
+                (0, 1, 1.0),
+                (1, 2, 2.5),
+                (0, 2, 6.0),
+                (0, 2, 3.0),
+                (2, 3, 1.0),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn edge_map(targets: [&[(NodeIndex, FiltrationTime)]; 5]) -> EdgeMap {
+        targets
+            .into_iter()
+            .enumerate()
+            .map(|(source, targets)| (source as NodeIndex, targets.iter().copied().collect()))
+            .collect()
+    }
+
+    #[test]
+    fn weight_keeps_the_lightest_parallel_edge() {
+        let expected = edge_map([&[(1, 1.0), (2, 3.0)], &[(2, 2.5)], &[(3, 1.0)], &[], &[]]);
+        assert_eq!(build_filtration(&graph(), FiltrationKind::Weight), expected);
+    }
+
+    #[test]
+    fn shortest_path_sums_weights() {
+        let expected = edge_map([
+            &[(1, 1.0), (2, 3.0), (3, 4.0)],
+            &[(2, 2.5), (3, 3.5)],
+            &[(3, 1.0)],
+            &[],
+            &[],
+        ]);
+        assert_eq!(
+            build_filtration(&graph(), FiltrationKind::ShortestPath),
+            expected
+        );
+    }
+
+    // 0 → 1 → 2 is longer than 0 → 2 but its heaviest edge is lighter
+    #[test]
+    fn minimax_takes_the_lightest_heaviest_edge() {
+        let expected = edge_map([
+            &[(1, 1.0), (2, 2.5), (3, 2.5)],
+            &[(2, 2.5), (3, 2.5)],
+            &[(3, 1.0)],
+            &[],
+            &[],
+        ]);
+        assert_eq!(
+            build_filtration(&graph(), FiltrationKind::Minimax),
+            expected
+        );
+    }
+
+    #[test]
+    fn hop_count_ignores_weights() {
+        let expected = edge_map([
+            &[(1, 1.0), (2, 1.0), (3, 2.0)],
+            &[(2, 1.0), (3, 2.0)],
+            &[(3, 1.0)],
+            &[],
+            &[],
+        ]);
+        assert_eq!(
+            build_filtration(&graph(), FiltrationKind::HopCount),
+            expected
+        );
+    }
+
+    #[test]
+    fn parses_names() {
+        for kind in [
+            FiltrationKind::ShortestPath,
+            FiltrationKind::Weight,
+            FiltrationKind::HopCount,
+            FiltrationKind::Minimax,
+        ] {
+            assert_eq!(kind.to_string().parse(), Ok(kind));
+        }
+        assert!("geodesic".parse::<FiltrationKind>().is_err());
+    }
+}
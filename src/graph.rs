@@ -0,0 +1,100 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{FiltrationTime, NodeIndex, Progress};
+
+/// A weighted digraph, as supplied by the user, before any filtration is applied.
+#[derive(Clone, Debug, Default)]
+pub struct WeightedDigraph {
+    pub nodes: Vec<NodeIndex>,
+    pub edges: Vec<(NodeIndex, NodeIndex, FiltrationTime)>,
+}
+
+impl WeightedDigraph {
+    /// Any endpoint of an edge that is missing from `nodes` is added.
+    /// Self-loops are skipped, since they play no part in path homology,
+    /// but their nodes are kept. Fails on negative or NaN weights.
+    pub fn new(
+        nodes: Vec<NodeIndex>,
+        edges: Vec<(NodeIndex, NodeIndex, FiltrationTime)>,
+    ) -> Result<Self, String> {
+        Ok(Self::new_counting_self_loops(nodes, edges)?.0)
+    }
+
+    /// As [`WeightedDigraph::new`], also returning the number of self-loops skipped, so that callers can report them.
+    pub fn new_counting_self_loops(
+        nodes: Vec<NodeIndex>,
+        mut edges: Vec<(NodeIndex, NodeIndex, FiltrationTime)>,
+    ) -> Result<(Self, usize), String> {
+        let mut seen: HashSet<NodeIndex> = HashSet::with_capacity(nodes.len());
+        let mut all_nodes = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            if seen.insert(node) {
+                all_nodes.push(node);
+            }
+        }
+        for &(i, j, weight) in edges.iter() {
+            if weight.is_nan() || weight < 0.0 {
+                return Err(format!("Edge ({i},{j}) has invalid weight {weight}"));
+            }
+            for endpoint in [i, j] {
+                if seen.insert(endpoint) {
+                    all_nodes.push(endpoint);
+                }
+            }
+        }
+        let n_edges = edges.len();
+        edges.retain(|&(i, j, _)| i != j);
+        let skipped = n_edges - edges.len();
+        Ok((
+            Self {
+                nodes: all_nodes,
+                edges,
+            },
+            skipped,
+        ))
+    }
+
+    /// Out-neighbours of every node, keeping the smallest weight among parallel edges.
+    pub fn out_neighbours(&self) -> HashMap<NodeIndex, Vec<(NodeIndex, FiltrationTime)>> {
+        let mut weights: HashMap<NodeIndex, HashMap<NodeIndex, FiltrationTime>> = self
+            .nodes
+            .iter()
+            .map(|&node| (node, HashMap::new()))
+            .collect();
+        for &(i, j, weight) in self.edges.iter() {
+            let entry = weights.get_mut(&i).unwrap().entry(j).or_insert(weight);
+            *entry = entry.min(weight);
+        }
+        weights
+            .into_iter()
+            .map(|(node, targets)| (node, targets.into_iter().collect()))
+            .collect()
+    }
+}
+
+/// Reports the number of self-loops skipped while building a graph, if any.
+pub(crate) fn report_self_loops(skipped: usize, progress: Progress) {
+    if skipped > 0 {
+        progress(&format!("Skipped {skipped} self-loops"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_self_loops() {
+        let edges = vec![(0, 1, 1.0), (2, 2, 1.0), (1, 1, 3.0)];
+        let (graph, skipped) = WeightedDigraph::new_counting_self_loops(vec![], edges).unwrap();
+        assert_eq!(graph.nodes, vec![0, 1, 2]);
+        assert_eq!(graph.edges, vec![(0, 1, 1.0)]);
+        assert_eq!(skipped, 2);
+    }
+
+    #[test]
+    fn rejects_invalid_weights() {
+        assert!(WeightedDigraph::new(vec![], vec![(0, 1, -1.0)]).is_err());
+        assert!(WeightedDigraph::new(vec![], vec![(0, 1, FiltrationTime::NAN)]).is_err());
+    }
+}
@@ -1,16 +1,89 @@
-use pyo3::prelude::*;
+use pyo3::{exceptions::PyValueError, prelude::*};
 
-use rayon::{iter::IterBridge, prelude::*};
+use rayon::prelude::*;
 
 use std::{cmp::Ordering, collections::HashMap};
 
 use crate::{
     columns::{ColumnType, GrpphatiRsColumn},
-    FiltrationTime, NodeIndex,
+    print_progress, FiltrationTime, NodeIndex, Progress,
 };
 
-type EdgeMap = HashMap<NodeIndex, HashMap<NodeIndex, FiltrationTime>>;
-type UnstructuredTwoPathWithTime = ((NodeIndex, NodeIndex, NodeIndex), FiltrationTime);
+pub type EdgeMap = HashMap<NodeIndex, HashMap<NodeIndex, FiltrationTime>>;
+pub type NodeMap = HashMap<NodeIndex, FiltrationTime>;
+pub(crate) type UnstructuredTwoPathWithTime = ((NodeIndex, NodeIndex, NodeIndex), FiltrationTime);
+
+/// The edges of a filtration in compressed sparse row form.
+/// Nodes are given dense indices by their position among the sorted labels,
+/// and the edges out of each node are sorted by target, so edge times are found by binary search.
+pub struct CsrEdgeMap {
+    // Sorted labels of every node with an edge in or out
+    labels: Vec<NodeIndex>,
+    // Edges out of dense node d are targets[offsets[d]..offsets[d + 1]]
+    offsets: Vec<usize>,
+    targets: Vec<usize>,
+    times: Vec<FiltrationTime>,
+}
+
+impl CsrEdgeMap {
+    pub fn new(edge_map: &EdgeMap) -> Self {
+        let mut labels: Vec<NodeIndex> = edge_map
+            .iter()
+            .flat_map(|(&i, targets)| std::iter::once(i).chain(targets.keys().copied()))
+            .collect();
+        labels.par_sort_unstable();
+        labels.dedup();
+        let dense = |node: &NodeIndex| labels.binary_search(node).unwrap();
+        let mut edges: Vec<(usize, usize, FiltrationTime)> = edge_map
+            .par_iter()
+            .flat_map_iter(|(i, targets)| {
+                let source = dense(i);
+                targets
+                    .iter()
+                    .map(move |(j, &time)| (source, dense(j), time))
+            })
+            .collect();
+        edges.par_sort_unstable_by_key(|&(source, target, _)| (source, target));
+        let mut offsets = vec![0; labels.len() + 1];
+        for &(source, _, _) in edges.iter() {
+            offsets[source + 1] += 1;
+        }
+        for d in 0..labels.len() {
+            offsets[d + 1] += offsets[d];
+        }
+        let (targets, times) = edges
+            .into_iter()
+            .map(|(_, target, time)| (target, time))
+            .unzip();
+        Self {
+            labels,
+            offsets,
+            targets,
+            times,
+        }
+    }
+
+    fn dense(&self, node: NodeIndex) -> Option<usize> {
+        self.labels.binary_search(&node).ok()
+    }
+
+    // Indices into targets and times of the edges out of dense node d
+    fn out_edges(&self, d: usize) -> std::ops::Range<usize> {
+        self.offsets[d]..self.offsets[d + 1]
+    }
+
+    /// Entrance time of the edge i → j, or infinity if it never enters.
+    pub fn edge_time(&self, i: NodeIndex, j: NodeIndex) -> FiltrationTime {
+        let (Some(source), Some(target)) = (self.dense(i), self.dense(j)) else {
+            return FiltrationTime::INFINITY;
+        };
+        let range = self.out_edges(source);
+        match self.targets[range.clone()].binary_search(&target) {
+            Ok(offset) => self.times[range.start + offset],
+            Err(_) => FiltrationTime::INFINITY,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum TwoPathType {
@@ -25,58 +98,125 @@ struct TwoPathWithTime {
 }
 
 #[derive(Default)]
-struct TwoPathFold {
+pub(crate) struct TwoPathFold {
     // Columns that are ready to be put in the basis
-    cols: Vec<GrpphatiRsColumn>,
+    pub(crate) cols: Vec<GrpphatiRsColumn>,
     // Bridges indexed by their endpoints, together with entrance times
-    bridges: HashMap<(NodeIndex, NodeIndex), Vec<(NodeIndex, FiltrationTime)>>,
+    pub(crate) bridges: HashMap<(NodeIndex, NodeIndex), Vec<(NodeIndex, FiltrationTime)>>,
 }
 
-fn compare_columns(col_a: &GrpphatiRsColumn, col_b: &GrpphatiRsColumn) -> Ordering {
+/// Orders columns by entrance time, breaking ties by dimension then column type,
+/// so that the order is the same on every run.
+pub fn compare_columns(col_a: &GrpphatiRsColumn, col_b: &GrpphatiRsColumn) -> Ordering {
     let t_a = col_a
         .entrance_time
         .expect("Produced columns should have an entrance time");
     let t_b = col_b
         .entrance_time
         .expect("Produced columns should have an entrance time");
-    t_a.partial_cmp(&t_b)
-        .expect("Neither filtration time should be NaN")
+    t_a.total_cmp(&t_b)
+        .then_with(|| col_a.dimension().cmp(&col_b.dimension()))
+        .then_with(|| col_a.col_type.cmp(&col_b.col_type))
+}
+
+/// Builds a basis for the 2-cells of regular path homology, sorted by entrance time.
+/// If `node_map` is provided then each cell enters at the maximum entrance time
+/// over its vertices and edges, otherwise nodes are assumed to be present from the start.
+/// The edge map need not be transitively closed: when the endpoints of some 2-paths are never
+/// joined by an edge, those 2-paths never collapse, so there is no triangle to fill them.
+#[pyfunction]
+#[pyo3(signature = (edge_map, node_map = None))]
+pub fn get_rph_two_cells(
+    edge_map: EdgeMap,
+    node_map: Option<NodeMap>,
+) -> PyResult<Vec<GrpphatiRsColumn>> {
+    if let Some(node_map) = &node_map {
+        check_edge_times(&edge_map, node_map).map_err(PyValueError::new_err)?;
+    }
+    Ok(build_path_two_cells(&edge_map, node_map.as_ref(), true))
 }
 
-/// Formats the sum of two numbers as string.
+/// Builds a basis for the 2-cells of non-regular path homology, sorted by entrance time.
+/// The boundary of a double edge i → j → i keeps the degenerate term i → i,
+/// so double edges only contribute through differences i → j → i - i → l → i,
+/// which are represented by [`ColumnType::DoubleSquare`].
+/// See [`get_rph_two_cells`] for the role of `node_map`.
 #[pyfunction]
-pub fn get_rph_two_cells(edge_map: EdgeMap) -> Vec<GrpphatiRsColumn> {
-    let two_path_iter = enumerate_two_paths(&edge_map);
-    let mut two_path_fold = split_off_bridges(&edge_map, two_path_iter);
+#[pyo3(signature = (edge_map, node_map = None))]
+pub fn get_nrph_two_cells(
+    edge_map: EdgeMap,
+    node_map: Option<NodeMap>,
+) -> PyResult<Vec<GrpphatiRsColumn>> {
+    if let Some(node_map) = &node_map {
+        check_edge_times(&edge_map, node_map).map_err(PyValueError::new_err)?;
+    }
+    Ok(build_path_two_cells(&edge_map, node_map.as_ref(), false))
+}
+
+pub fn build_path_two_cells(
+    edge_map: &EdgeMap,
+    node_map: Option<&NodeMap>,
+    regular: bool,
+) -> Vec<GrpphatiRsColumn> {
+    build_path_two_cells_with(edge_map, node_map, regular, &print_progress)
+}
+
+/// As [`build_path_two_cells`], reporting to `progress`.
+pub fn build_path_two_cells_with(
+    edge_map: &EdgeMap,
+    node_map: Option<&NodeMap>,
+    regular: bool,
+    progress: Progress,
+) -> Vec<GrpphatiRsColumn> {
+    let csr = CsrEdgeMap::new(edge_map);
+    let two_path_iter = enumerate_two_paths(&csr, node_map);
+    let edge_time = |i, j| csr.edge_time(i, j);
+    let mut two_path_fold = split_off_bridges(edge_time, two_path_iter, regular);
     // Add columns arising from bridges
-    let sorted_bridges = two_path_fold
-        .bridges
-        .into_iter()
-        .par_bridge()
-        .map(|(endpoints, bridges)| (endpoints, sort_bridges(bridges)));
-    // TODO: Make this neater and in paralell?
-    let bridge_cols = sorted_bridges
-        .map(|(endpoints, bridges)| build_bridge_columns(&edge_map, endpoints, bridges));
-    let (long_square_cols, triangle_cols): (Vec<_>, Vec<_>) = bridge_cols.unzip();
+    let bridges: Vec<_> = two_path_fold.bridges.into_iter().collect();
+    let (long_square_cols, triangle_cols): (Vec<_>, Vec<_>) = bridges
+        .into_par_iter()
+        .map(|(endpoints, bridges)| {
+            let collapse_time = csr.edge_time(endpoints.0, endpoints.1);
+            build_bridge_columns(collapse_time, endpoints, sort_bridges(bridges))
+        })
+        .unzip();
     let long_square_cols: Vec<_> = long_square_cols.into_iter().flatten().collect();
     let triangle_cols: Vec<_> = triangle_cols.into_iter().flatten().collect();
     two_path_fold.cols.extend(triangle_cols);
     two_path_fold.cols.extend(long_square_cols);
-    println!("Computed 2-cells");
+    progress("Computed 2-cells");
     two_path_fold.cols.sort_unstable_by(compare_columns);
-    println!("Sorted 2-cells");
+    progress("Sorted 2-cells");
     two_path_fold.cols
 }
 
+/// Builds the 2-cells of the directed flag complex, sorted by entrance time.
+/// See [`get_rph_two_cells`] for the role of `node_map`.
 #[pyfunction]
-pub fn get_dflag_two_cells(edge_map: EdgeMap) -> Vec<GrpphatiRsColumn> {
-    let two_path_iter = enumerate_two_paths(&edge_map);
+#[pyo3(signature = (edge_map, node_map = None))]
+pub fn get_dflag_two_cells(
+    edge_map: EdgeMap,
+    node_map: Option<NodeMap>,
+) -> PyResult<Vec<GrpphatiRsColumn>> {
+    if let Some(node_map) = &node_map {
+        check_edge_times(&edge_map, node_map).map_err(PyValueError::new_err)?;
+    }
+    Ok(build_dflag_two_cells(&edge_map, node_map.as_ref()))
+}
+
+pub fn build_dflag_two_cells(
+    edge_map: &EdgeMap,
+    node_map: Option<&NodeMap>,
+) -> Vec<GrpphatiRsColumn> {
+    let csr = CsrEdgeMap::new(edge_map);
+    let two_path_iter = enumerate_two_paths(&csr, node_map);
     let mut cols: Vec<_> = two_path_iter
         .filter_map(|(path, path_time)| {
             if path.0 == path.2 {
                 return None;
             }
-            let ac_time = edge_time(&edge_map, (&path.0, &path.2));
+            let ac_time = csr.edge_time(path.0, path.2);
             let entrance_time = path_time.max(ac_time);
             if entrance_time.is_infinite() {
                 return None;
@@ -91,39 +231,98 @@ pub fn get_dflag_two_cells(edge_map: EdgeMap) -> Vec<GrpphatiRsColumn> {
     cols
 }
 
-fn enumerate_two_paths<'a>(
-    edge_map: &'a EdgeMap,
-) -> IterBridge<impl Iterator<Item = UnstructuredTwoPathWithTime> + Send + 'a> {
-    edge_map
+/// Builds the 1-cells of the filtration, sorted by entrance time,
+/// after checking that no edge enters before its endpoints.
+#[pyfunction]
+#[pyo3(signature = (edge_map, node_map = None))]
+pub fn get_one_cells(
+    edge_map: EdgeMap,
+    node_map: Option<NodeMap>,
+) -> PyResult<Vec<GrpphatiRsColumn>> {
+    if let Some(node_map) = &node_map {
+        check_edge_times(&edge_map, node_map).map_err(PyValueError::new_err)?;
+    }
+    let mut cols: Vec<_> = edge_map
         .iter()
-        .flat_map(move |(&source, dists_from_source)| {
-            dists_from_source
-                .iter()
-                .flat_map(move |(midpoint, first_hop)| {
-                    edge_map
-                        .get(midpoint)
-                        .unwrap()
-                        .iter()
-                        .map(move |(&endpoint, &second_hop)| {
-                            ((source, *midpoint, endpoint), first_hop.max(second_hop))
-                        })
-                })
+        .flat_map(|(&i, targets)| {
+            targets.iter().map(move |(&j, &time)| GrpphatiRsColumn {
+                col_type: ColumnType::Edge(i, j),
+                entrance_time: Some(time),
+            })
         })
-        .par_bridge()
+        .collect();
+    cols.sort_unstable_by(compare_columns);
+    Ok(cols)
 }
 
-fn split_off_bridges(
-    edge_map: &EdgeMap,
-    two_paths: IterBridge<impl Iterator<Item = UnstructuredTwoPathWithTime> + Send>,
+// Every edge must enter at or after both of its endpoints
+pub fn check_edge_times(edge_map: &EdgeMap, node_map: &NodeMap) -> Result<(), String> {
+    for (&i, targets) in edge_map.iter() {
+        for (&j, &time) in targets.iter() {
+            for endpoint in [i, j] {
+                let Some(&node_time) = node_map.get(&endpoint) else {
+                    return Err(format!(
+                        "Node {endpoint} of edge ({i},{j}) has no entrance time"
+                    ));
+                };
+                if time < node_time {
+                    return Err(format!(
+                        "Edge ({i},{j}) enters at {time}, before node {endpoint} at {node_time}"
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// All 2-paths i → j → k in the filtration, including double edges, with their entrance times.
+/// Parallel over the source nodes and then over their out-edges, so that work is balanced around hubs.
+pub fn enumerate_two_paths<'a>(
+    csr: &'a CsrEdgeMap,
+    node_map: Option<&'a NodeMap>,
+) -> impl ParallelIterator<Item = UnstructuredTwoPathWithTime> + 'a {
+    (0..csr.labels.len()).into_par_iter().flat_map(move |s| {
+        let source = csr.labels[s];
+        csr.out_edges(s)
+            .into_par_iter()
+            .flat_map_iter(move |first| {
+                let m = csr.targets[first];
+                let midpoint = csr.labels[m];
+                let first_hop = csr.times[first];
+                csr.out_edges(m).map(move |second| {
+                    let endpoint = csr.labels[csr.targets[second]];
+                    let path_time = first_hop
+                        .max(csr.times[second])
+                        .max(node_time(node_map, source))
+                        .max(node_time(node_map, midpoint))
+                        .max(node_time(node_map, endpoint));
+                    ((source, midpoint, endpoint), path_time)
+                })
+            })
+    })
+}
+
+// Sorts 2-paths into columns and bridges, where `edge_time` gives the entrance time of each edge.
+// In the non-regular theory, double edges i → j → i are treated as bridges from i to i
+pub(crate) fn split_off_bridges(
+    edge_time: impl Fn(NodeIndex, NodeIndex) -> FiltrationTime + Sync,
+    two_paths: impl ParallelIterator<Item = UnstructuredTwoPathWithTime>,
+    regular: bool,
 ) -> TwoPathFold {
     // Split off two paths that automatically lead to columns
     let typed_paths = two_paths.map(|(two_path, path_time)| {
-        if two_path.0 == two_path.2 {
+        if two_path.0 == two_path.2 && !regular {
+            TwoPathWithTime {
+                two_path: TwoPathType::Bridge((two_path.0, two_path.2), two_path.1),
+                entrance_time: path_time,
+            }
+        } else if two_path.0 == two_path.2 {
             TwoPathWithTime {
                 two_path: TwoPathType::DoubleEdge(two_path.0, two_path.1),
                 entrance_time: path_time,
             }
-        } else if edge_time(edge_map, (&two_path.0, &two_path.2)) <= path_time {
+        } else if edge_time(two_path.0, two_path.2) <= path_time {
             TwoPathWithTime {
                 two_path: TwoPathType::Triangle(two_path.0, two_path.1, two_path.2),
                 entrance_time: path_time,
@@ -138,7 +337,7 @@ fn split_off_bridges(
     // In parallel build up the bridges hashmap and cols vector
     // The paths get split across threads and folded in each thread
     let folded = typed_paths.fold(
-        || TwoPathFold::default(),
+        TwoPathFold::default,
         |mut accum: TwoPathFold, timed_path: TwoPathWithTime| {
             match timed_path.two_path {
                 TwoPathType::Bridge(endpoints, j) => {
@@ -162,9 +361,9 @@ fn split_off_bridges(
     );
     // Do a final reduce to join the folds made be each thread
     let reduced = folded.reduce(
-        || TwoPathFold::default(),
+        TwoPathFold::default,
         |mut accum: TwoPathFold, next_fold: TwoPathFold| {
-            accum.cols.extend(next_fold.cols.into_iter());
+            accum.cols.extend(next_fold.cols);
             for (endpoints, fold_bridges) in next_fold.bridges.into_iter() {
                 if let Some(accum_bridges) = accum.bridges.get_mut(&endpoints) {
                     accum_bridges.extend(fold_bridges);
@@ -178,23 +377,36 @@ fn split_off_bridges(
     reduced
 }
 
-fn sort_bridges(mut bridges: Vec<(NodeIndex, FiltrationTime)>) -> Vec<(NodeIndex, FiltrationTime)> {
-    bridges.sort_by(|b1, b2| b1.1.partial_cmp(&b2.1).unwrap());
+/// Sorts bridges by entrance time then midpoint, so the first, which is the base of the long squares,
+/// is chosen canonically.
+pub fn sort_bridges(
+    mut bridges: Vec<(NodeIndex, FiltrationTime)>,
+) -> Vec<(NodeIndex, FiltrationTime)> {
+    bridges.sort_unstable_by(|b1, b2| b1.1.total_cmp(&b2.1).then(b1.0.cmp(&b2.0)));
     bridges
 }
 
-fn build_bridge_columns(
-    edge_map: &EdgeMap,
+/// Builds the cells from the bridges between `endpoints`, which must be sorted by [`sort_bridges`],
+/// where `collapse_time` is the entrance time of the edge joining the endpoints.
+/// If it is infinite the endpoints are never joined, so there is no collapsing triangle,
+/// which would otherwise have a face missing from the complex.
+pub fn build_bridge_columns(
+    collapse_time: FiltrationTime,
     endpoints: (NodeIndex, NodeIndex),
     bridges: Vec<(NodeIndex, FiltrationTime)>,
 ) -> (Vec<GrpphatiRsColumn>, Vec<GrpphatiRsColumn>) {
     let mut bridge_iter = bridges.into_iter();
     let first_bridge = bridge_iter.next().expect("Found empty bridge vector");
-    let collapse_time = edge_time(edge_map, (&endpoints.0, &endpoints.1));
-    let collapsing_col = GrpphatiRsColumn {
-        col_type: ColumnType::Triangle(endpoints.0, first_bridge.0, endpoints.1),
-        entrance_time: Some(collapse_time),
-    };
+    if endpoints.0 == endpoints.1 {
+        // Double edges based at the same node never collapse
+        let ds_columns = bridge_iter
+            .map(|(bridge, time)| GrpphatiRsColumn {
+                col_type: ColumnType::DoubleSquare(endpoints.0, (first_bridge.0, bridge)),
+                entrance_time: Some(time),
+            })
+            .collect();
+        return (ds_columns, vec![]);
+    }
     let mut ls_columns = vec![];
     for (bridge, time) in bridge_iter {
         ls_columns.push(GrpphatiRsColumn {
@@ -202,12 +414,182 @@ fn build_bridge_columns(
             entrance_time: Some(time),
         })
     }
+    // If the endpoints are never joined by an edge, the bridges never collapse
+    if collapse_time.is_infinite() {
+        return (ls_columns, vec![]);
+    }
+    let collapsing_col = GrpphatiRsColumn {
+        col_type: ColumnType::Triangle(endpoints.0, first_bridge.0, endpoints.1),
+        entrance_time: Some(collapse_time),
+    };
     (ls_columns, vec![collapsing_col])
 }
 
-fn edge_time(edge_map: &EdgeMap, edge: (&NodeIndex, &NodeIndex)) -> FiltrationTime {
-    *edge_map
-        .get(edge.0)
-        .and_then(|dist_map| dist_map.get(edge.1))
-        .unwrap_or(&FiltrationTime::INFINITY)
+// Nodes without a node map are present from the start of the filtration
+pub fn node_time(node_map: Option<&NodeMap>, node: NodeIndex) -> FiltrationTime {
+    node_map
+        .and_then(|node_map| node_map.get(&node))
+        .copied()
+        .unwrap_or(FiltrationTime::NEG_INFINITY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge_map(edges: &[(NodeIndex, NodeIndex, FiltrationTime)]) -> EdgeMap {
+        let mut edge_map = EdgeMap::new();
+        for &(i, j, time) in edges {
+            edge_map.entry(i).or_default().insert(j, time);
+        }
+        edge_map
+    }
+
+    fn col_types(cols: &[GrpphatiRsColumn]) -> Vec<ColumnType> {
+        cols.iter().map(|col| col.col_type).collect()
+    }
+
+    #[test]
+    fn bridges_collapse_when_endpoints_are_joined() {
+        let edges = [
+            (0, 1, 0.0),
+            (1, 2, 0.0),
+            (0, 3, 1.0),
+            (3, 2, 1.0),
+            (0, 2, 2.0),
+        ];
+        let cols = build_path_two_cells(&edge_map(&edges), None, true);
+        assert_eq!(
+            col_types(&cols),
+            vec![
+                ColumnType::LongSquare(0, (1, 3), 2),
+                ColumnType::Triangle(0, 1, 2)
+            ]
+        );
+        assert_eq!(cols[1].entrance_time, Some(2.0));
+    }
+
+    // As in a weight filtration, which is not transitively closed
+    #[test]
+    fn bridges_never_collapse_without_an_edge() {
+        let edges = [(0, 1, 0.0), (1, 2, 0.0), (0, 3, 1.0), (3, 2, 1.0)];
+        let cols = build_path_two_cells(&edge_map(&edges), None, true);
+        assert_eq!(col_types(&cols), vec![ColumnType::LongSquare(0, (1, 3), 2)]);
+        let cols = build_path_two_cells(&edge_map(&edges[..2]), None, true);
+        assert!(cols.is_empty());
+    }
+
+    // A hub 0 joined both ways to 1..=20, which also form a path, with varied times
+    fn hub() -> (EdgeMap, NodeMap) {
+        let mut edges = vec![];
+        for node in 1..=20 {
+            edges.push((0, node, (node % 4) as FiltrationTime));
+            edges.push((node, 0, (node % 3) as FiltrationTime));
+            if node < 20 {
+                edges.push((node, node + 1, 1.5));
+            }
+        }
+        let node_map = (0..=20)
+            .map(|node| (node, (node % 2) as FiltrationTime))
+            .collect();
+        (edge_map(&edges), node_map)
+    }
+
+    // The 2-paths found by iterating over the nested maps
+    fn hash_map_two_paths(
+        edge_map: &EdgeMap,
+        node_map: Option<&NodeMap>,
+    ) -> Vec<UnstructuredTwoPathWithTime> {
+        let mut paths = vec![];
+        for (&s, targets) in edge_map.iter() {
+            for (&m, &first_hop) in targets.iter() {
+                for (&t, &second_hop) in edge_map.get(&m).into_iter().flatten() {
+                    let time = [s, m, t]
+                        .iter()
+                        .map(|&node| node_time(node_map, node))
+                        .fold(first_hop.max(second_hop), FiltrationTime::max);
+                    paths.push(((s, m, t), time));
+                }
+            }
+        }
+        paths
+    }
+
+    fn sorted(mut paths: Vec<UnstructuredTwoPathWithTime>) -> Vec<UnstructuredTwoPathWithTime> {
+        paths.sort_by_key(|path| path.0);
+        paths
+    }
+
+    #[test]
+    fn csr_matches_nested_maps() {
+        let (edge_map, node_map) = hub();
+        let csr = CsrEdgeMap::new(&edge_map);
+        for node_map in [None, Some(&node_map)] {
+            let expected = sorted(hash_map_two_paths(&edge_map, node_map));
+            let found = sorted(enumerate_two_paths(&csr, node_map).collect());
+            assert_eq!(found, expected);
+        }
+        for i in 0..=22 {
+            for j in 0..=22 {
+                let expected = edge_map
+                    .get(&i)
+                    .and_then(|targets| targets.get(&j))
+                    .copied()
+                    .unwrap_or(FiltrationTime::INFINITY);
+                assert_eq!(csr.edge_time(i, j), expected);
+            }
+        }
+    }
+
+    // Four bridges from 0 to 5 and from 6 to 5, and two from 6 to 0, all entering together,
+    // with double edges at 0 whose triangles to 5 tie with the collapse of the bridges from 0
+    fn tied_edges() -> Vec<(NodeIndex, NodeIndex, FiltrationTime)> {
+        let mut edges = vec![(0, 5, 2.0), (1, 0, 1.0), (2, 0, 1.0)];
+        for mid in 1..=4 {
+            edges.extend([(0, mid, 1.0), (6, mid, 1.0), (mid, 5, 1.0)]);
+        }
+        edges
+    }
+
+    #[test]
+    fn cells_are_reproducible() {
+        let mut edges = tied_edges();
+        for regular in [true, false] {
+            let expected = build_path_two_cells(&edge_map(&edges), None, regular);
+            for _ in 0..5 {
+                edges.reverse();
+                edges.rotate_left(3);
+                let cols = build_path_two_cells(&edge_map(&edges), None, regular);
+                assert_eq!(col_types(&cols), col_types(&expected));
+                let times: Vec<_> = cols.iter().map(|col| col.entrance_time).collect();
+                let expected_times: Vec<_> = expected.iter().map(|col| col.entrance_time).collect();
+                assert_eq!(times, expected_times);
+            }
+        }
+    }
+
+    // Ties are broken by dimension then cell, and the base of the long squares is the smallest midpoint
+    #[test]
+    fn ties_are_broken_canonically() {
+        let cols = build_path_two_cells(&edge_map(&tied_edges()), None, true);
+        assert_eq!(
+            col_types(&cols),
+            vec![
+                ColumnType::DoubleEdge(0, 1),
+                ColumnType::DoubleEdge(0, 2),
+                ColumnType::DoubleEdge(1, 0),
+                ColumnType::DoubleEdge(2, 0),
+                ColumnType::LongSquare(0, (1, 2), 5),
+                ColumnType::LongSquare(0, (1, 3), 5),
+                ColumnType::LongSquare(0, (1, 4), 5),
+                ColumnType::LongSquare(6, (1, 2), 0),
+                ColumnType::LongSquare(6, (1, 2), 5),
+                ColumnType::LongSquare(6, (1, 3), 5),
+                ColumnType::LongSquare(6, (1, 4), 5),
+                ColumnType::Triangle(0, 1, 5),
+                ColumnType::Triangle(1, 0, 5),
+                ColumnType::Triangle(2, 0, 5),
+            ]
+        );
+    }
 }
@@ -0,0 +1,357 @@
+use std::collections::{HashMap, HashSet};
+
+use pyo3::{exceptions::PyValueError, prelude::*};
+use rayon::prelude::*;
+
+use crate::{
+    columns::{ColumnType, GrpphatiRsColumn},
+    homology::{
+        build_bridge_columns, check_edge_times, compare_columns, enumerate_two_paths, node_time,
+        sort_bridges, split_off_bridges, CsrEdgeMap, EdgeMap, NodeMap, TwoPathFold,
+    },
+    FiltrationTime, NodeIndex,
+};
+
+type Endpoints = (NodeIndex, NodeIndex);
+type Bridges = Vec<(NodeIndex, FiltrationTime)>;
+
+/// The 2-cells of regular path homology for an evolving filtration.
+/// Keeps the bridges and cells for each pair of endpoints, so that when an edge is added, reweighted or removed
+/// only the cells with the same endpoints as a 2-path through that edge are rebuilt.
+#[pyclass]
+pub struct RphComplex {
+    edge_map: EdgeMap,
+    // Reverse of edge_map, so we can find 2-paths ending in an edge
+    in_edges: EdgeMap,
+    node_map: Option<NodeMap>,
+    // The bridges between each pair of endpoints, sorted so that the first is the base
+    bridges: HashMap<Endpoints, Bridges>,
+    // The cells built from the 2-paths with each pair of endpoints
+    cells: HashMap<Endpoints, Vec<GrpphatiRsColumn>>,
+}
+
+#[pymethods]
+impl RphComplex {
+    #[new]
+    #[pyo3(signature = (edge_map, node_map = None))]
+    fn new_py(edge_map: EdgeMap, node_map: Option<NodeMap>) -> PyResult<Self> {
+        Self::new(edge_map, node_map).map_err(PyValueError::new_err)
+    }
+
+    /// Adds the edge i → j, entering at `time`.
+    /// Fails if the edge is already present.
+    #[pyo3(name = "add_edge")]
+    fn add_edge_py(&mut self, i: NodeIndex, j: NodeIndex, time: FiltrationTime) -> PyResult<()> {
+        self.add_edge(i, j, time).map_err(PyValueError::new_err)
+    }
+
+    /// Changes the entrance time of the existing edge i → j.
+    #[pyo3(name = "update_time")]
+    fn update_time_py(&mut self, i: NodeIndex, j: NodeIndex, time: FiltrationTime) -> PyResult<()> {
+        self.update_time(i, j, time).map_err(PyValueError::new_err)
+    }
+
+    /// Removes the existing edge i → j.
+    #[pyo3(name = "remove_edge")]
+    fn remove_edge_py(&mut self, i: NodeIndex, j: NodeIndex) -> PyResult<()> {
+        self.remove_edge(i, j).map_err(PyValueError::new_err)
+    }
+
+    /// All 2-cells, sorted by entrance time.
+    pub fn columns(&self) -> Vec<GrpphatiRsColumn> {
+        let mut cols: Vec<_> = self.cells.values().flatten().cloned().collect();
+        cols.par_sort_unstable_by(compare_columns);
+        cols
+    }
+
+    fn __len__(&self) -> usize {
+        self.cells.values().map(|cells| cells.len()).sum()
+    }
+}
+
+impl RphComplex {
+    pub fn new(edge_map: EdgeMap, node_map: Option<NodeMap>) -> Result<Self, String> {
+        for (&i, targets) in edge_map.iter() {
+            for (&j, &time) in targets.iter() {
+                check_time(i, j, time)?;
+            }
+        }
+        if let Some(node_map) = &node_map {
+            if let Some((node, time)) = node_map.iter().find(|(_, time)| !is_valid_time(**time)) {
+                return Err(format!("Node {node} has invalid time {time}"));
+            }
+            check_edge_times(&edge_map, node_map)?;
+        }
+        let mut in_edges: EdgeMap = edge_map
+            .keys()
+            .map(|&node| (node, HashMap::new()))
+            .collect();
+        for (&i, targets) in edge_map.iter() {
+            for (&j, &time) in targets.iter() {
+                in_edges.entry(j).or_default().insert(i, time);
+            }
+        }
+        let csr = CsrEdgeMap::new(&edge_map);
+        let fold = split_off_bridges(
+            |i, j| csr.edge_time(i, j),
+            enumerate_two_paths(&csr, node_map.as_ref()),
+            true,
+        );
+        let mut complex = Self {
+            edge_map,
+            in_edges,
+            node_map,
+            bridges: HashMap::new(),
+            cells: HashMap::new(),
+        };
+        complex.insert_fold(fold);
+        Ok(complex)
+    }
+
+    pub fn add_edge(
+        &mut self,
+        i: NodeIndex,
+        j: NodeIndex,
+        time: FiltrationTime,
+    ) -> Result<(), String> {
+        if self.has_edge(i, j) {
+            return Err(format!(
+                "Edge ({i},{j}) is already present, use update_time instead"
+            ));
+        }
+        self.set_edge_time(i, j, time)
+    }
+
+    pub fn update_time(
+        &mut self,
+        i: NodeIndex,
+        j: NodeIndex,
+        time: FiltrationTime,
+    ) -> Result<(), String> {
+        if !self.has_edge(i, j) {
+            return Err(format!(
+                "Edge ({i},{j}) is not present, use add_edge instead"
+            ));
+        }
+        self.set_edge_time(i, j, time)
+    }
+
+    pub fn remove_edge(&mut self, i: NodeIndex, j: NodeIndex) -> Result<(), String> {
+        if !self.has_edge(i, j) {
+            return Err(format!("Edge ({i},{j}) is not present"));
+        }
+        self.edge_map.get_mut(&i).unwrap().remove(&j);
+        self.in_edges.get_mut(&j).unwrap().remove(&i);
+        let affected = self.affected_endpoints(i, j);
+        self.rebuild(affected);
+        Ok(())
+    }
+
+    fn has_edge(&self, i: NodeIndex, j: NodeIndex) -> bool {
+        self.edge_map
+            .get(&i)
+            .is_some_and(|targets| targets.contains_key(&j))
+    }
+
+    fn set_edge_time(
+        &mut self,
+        i: NodeIndex,
+        j: NodeIndex,
+        time: FiltrationTime,
+    ) -> Result<(), String> {
+        if i == j {
+            return Err(format!("Self-loop at node {i} is not supported"));
+        }
+        check_time(i, j, time)?;
+        if let Some(node_map) = &self.node_map {
+            for endpoint in [i, j] {
+                match node_map.get(&endpoint) {
+                    None => {
+                        return Err(format!(
+                            "Node {endpoint} of edge ({i},{j}) has no entrance time"
+                        ))
+                    }
+                    Some(&node_time) if time < node_time => {
+                        return Err(format!(
+                            "Edge ({i},{j}) enters at {time}, before node {endpoint} at {node_time}"
+                        ))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for node in [i, j] {
+            self.edge_map.entry(node).or_default();
+            self.in_edges.entry(node).or_default();
+        }
+        self.edge_map.get_mut(&i).unwrap().insert(j, time);
+        self.in_edges.get_mut(&j).unwrap().insert(i, time);
+        let affected = self.affected_endpoints(i, j);
+        self.rebuild(affected);
+        Ok(())
+    }
+
+    // The edge i → j lies on the 2-paths i → j → t and s → i → j,
+    // and decides whether the 2-paths from i to j form triangles
+    fn affected_endpoints(&self, i: NodeIndex, j: NodeIndex) -> Vec<Endpoints> {
+        let mut affected = HashSet::from([(i, j)]);
+        let out_of_j = self.edge_map.get(&j).into_iter().flat_map(HashMap::keys);
+        let into_i = self.in_edges.get(&i).into_iter().flat_map(HashMap::keys);
+        affected.extend(out_of_j.map(|&t| (i, t)));
+        affected.extend(into_i.map(|&s| (s, j)));
+        affected.into_iter().collect()
+    }
+
+    // Rebuilds the bridges and cells for each of the given endpoints from scratch
+    fn rebuild(&mut self, endpoints: Vec<Endpoints>) {
+        for endpoints in endpoints.iter() {
+            self.bridges.remove(endpoints);
+            self.cells.remove(endpoints);
+        }
+        let edge_map = &self.edge_map;
+        let node_map = self.node_map.as_ref();
+        let two_paths = endpoints.into_par_iter().flat_map_iter(|(s, t)| {
+            let out_of_s = edge_map.get(&s).into_iter().flatten();
+            out_of_s.filter_map(move |(&m, &first_hop)| {
+                let second_hop = *edge_map.get(&m)?.get(&t)?;
+                let path_time = [s, m, t]
+                    .iter()
+                    .map(|&node| node_time(node_map, node))
+                    .fold(first_hop.max(second_hop), FiltrationTime::max);
+                Some(((s, m, t), path_time))
+            })
+        });
+        let fold = split_off_bridges(|i, j| edge_time(edge_map, i, j), two_paths, true);
+        self.insert_fold(fold);
+    }
+
+    // Stores the columns and bridges of `fold`, building the cells of each bridge as in `build_path_two_cells`
+    fn insert_fold(&mut self, fold: TwoPathFold) {
+        for col in fold.cols {
+            self.cells
+                .entry(endpoints(col.col_type))
+                .or_default()
+                .push(col);
+        }
+        for (endpoints, bridges) in fold.bridges {
+            let bridges = sort_bridges(bridges);
+            let collapse_time = edge_time(&self.edge_map, endpoints.0, endpoints.1);
+            let (long_square_cols, triangle_cols) =
+                build_bridge_columns(collapse_time, endpoints, bridges.clone());
+            let cells = self.cells.entry(endpoints).or_default();
+            cells.extend(triangle_cols);
+            cells.extend(long_square_cols);
+            self.bridges.insert(endpoints, bridges);
+        }
+    }
+}
+
+// The edge map changes with every update, so edge times are looked up in it rather than in a `CsrEdgeMap`
+fn edge_time(edge_map: &EdgeMap, i: NodeIndex, j: NodeIndex) -> FiltrationTime {
+    edge_map
+        .get(&i)
+        .and_then(|targets| targets.get(&j))
+        .copied()
+        .unwrap_or(FiltrationTime::INFINITY)
+}
+
+// Times are sorted when building cells, so must be comparable, and are non-negative as weights are
+fn is_valid_time(time: FiltrationTime) -> bool {
+    time >= 0.0
+}
+
+fn check_time(i: NodeIndex, j: NodeIndex, time: FiltrationTime) -> Result<(), String> {
+    if !is_valid_time(time) {
+        return Err(format!("Edge ({i},{j}) has invalid time {time}"));
+    }
+    Ok(())
+}
+
+// The endpoints of the 2-paths from which a 2-cell is built
+fn endpoints(col_type: ColumnType) -> Endpoints {
+    match col_type {
+        ColumnType::DoubleEdge(i, _) | ColumnType::DoubleSquare(i, _) => (i, i),
+        ColumnType::Triangle(s, _, t) | ColumnType::LongSquare(s, _, t) => (s, t),
+        ColumnType::Edge(_, _) | ColumnType::Node(_) => {
+            unreachable!("Only 2-cells are built from 2-paths")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::homology::build_path_two_cells;
+
+    fn summary(cols: &[GrpphatiRsColumn]) -> Vec<(ColumnType, Option<FiltrationTime>)> {
+        cols.iter()
+            .map(|col| (col.col_type, col.entrance_time))
+            .collect()
+    }
+
+    fn assert_matches_rebuild(complex: &RphComplex) {
+        let expected = build_path_two_cells(&complex.edge_map, complex.node_map.as_ref(), true);
+        assert_eq!(summary(&complex.columns()), summary(&expected));
+    }
+
+    // Adds, reweights and removes edges among 6 nodes in a fixed pseudo-random order,
+    // with few distinct times so that bases and triangles change often
+    #[test]
+    fn updates_match_rebuild() {
+        let node_map = NodeMap::from_iter((0..6).map(|node| (node, 0.0)));
+        for node_map in [None, Some(node_map)] {
+            let edge_map = EdgeMap::from([(0, HashMap::from([(1, 1.0)]))]);
+            let mut complex = RphComplex::new(edge_map, node_map).unwrap();
+            let mut state: u64 = 7;
+            for _ in 0..300 {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let (i, j) = ((state >> 33) % 6, (state >> 41) % 6);
+                let (i, j) = (i as NodeIndex, j as NodeIndex);
+                let time = ((state >> 49) % 4) as FiltrationTime;
+                if i == j {
+                    continue;
+                }
+                let present = complex
+                    .edge_map
+                    .get(&i)
+                    .is_some_and(|targets| targets.contains_key(&j));
+                match (present, (state >> 20) % 3) {
+                    (false, _) => complex.add_edge(i, j, time).unwrap(),
+                    (true, 0) => complex.remove_edge(i, j).unwrap(),
+                    (true, _) => complex.update_time(i, j, time).unwrap(),
+                }
+                assert_matches_rebuild(&complex);
+            }
+            assert!(complex.bridges.values().all(|bridges| !bridges.is_empty()));
+        }
+    }
+
+    #[test]
+    fn rejects_missing_edges() {
+        let mut complex = RphComplex::new(EdgeMap::new(), None).unwrap();
+        assert!(complex.update_time(0, 1, 1.0).is_err());
+        assert!(complex.remove_edge(0, 1).is_err());
+        complex.add_edge(0, 1, 1.0).unwrap();
+        assert!(complex.add_edge(0, 1, 2.0).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_times() {
+        let node_map = NodeMap::from_iter((0..3).map(|node| (node, 0.0)));
+        for node_map in [None, Some(node_map)] {
+            let mut complex = RphComplex::new(EdgeMap::new(), node_map).unwrap();
+            assert!(complex.add_edge(0, 1, FiltrationTime::NAN).is_err());
+            assert!(complex.add_edge(0, 1, -1.0).is_err());
+            complex.add_edge(0, 1, 1.0).unwrap();
+            assert!(complex.update_time(0, 1, FiltrationTime::NAN).is_err());
+            assert!(complex.update_time(0, 1, -1.0).is_err());
+            assert_eq!(complex.edge_map[&0][&1], 1.0);
+        }
+        let edge_map = EdgeMap::from([(0, HashMap::from([(1, FiltrationTime::NAN)]))]);
+        assert!(RphComplex::new(edge_map, None).is_err());
+        let node_map = NodeMap::from([(0, FiltrationTime::NAN)]);
+        assert!(RphComplex::new(EdgeMap::new(), Some(node_map)).is_err());
+    }
+}
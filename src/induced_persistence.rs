@@ -0,0 +1,530 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use itertools::Itertools;
+use pyo3::{exceptions::PyValueError, prelude::*};
+use rayon::prelude::*;
+
+use crate::{
+    columns::GrpphatiRsColumn,
+    compute_mapping::{compute_dflag_inclusion, ChainMapError},
+    invariants::Bar,
+    sparsifiers::FaceIndex,
+    FiltrationTime,
+};
+
+/// The barcodes of the image, kernel and cokernel of a map induced on homology, including bars of length zero.
+#[pyclass(get_all)]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InducedBarcodes {
+    /// Classes of the domain which survive in the codomain.
+    pub image: Vec<Bar>,
+    /// Classes of the domain which have become boundaries in the codomain.
+    pub kernel: Vec<Bar>,
+    /// Classes of the codomain which do not come from the domain.
+    pub cokernel: Vec<Bar>,
+}
+
+#[pymethods]
+impl InducedBarcodes {
+    fn __repr__(&self) -> String {
+        format!(
+            "InducedBarcodes(image={}, kernel={}, cokernel={} bars)",
+            self.image.len(),
+            self.kernel.len(),
+            self.cokernel.len()
+        )
+    }
+}
+
+/// The image, kernel and cokernel persistence of the map on homology in `dimension` induced by including
+/// the directed flag complex `dflag_cells` into the regular path complex `rph_cells` of the same filtration,
+/// e.g. the cells returned by `grpdflh_pipeline` and `grpph_pipeline` without wedge, prune or truncation.
+/// The kernel holds the flag complex classes which path homology fills in.
+/// The two complexes share their 1-skeleton, so in dimension 1 the map is onto and the cokernel is empty.
+#[pyfunction]
+#[pyo3(name = "dflag_inclusion_persistence")]
+#[pyo3(signature = (dflag_cells, rph_cells, dimension = 1))]
+pub fn dflag_inclusion_persistence_py(
+    py: Python<'_>,
+    dflag_cells: Vec<GrpphatiRsColumn>,
+    rph_cells: Vec<GrpphatiRsColumn>,
+    dimension: usize,
+) -> PyResult<InducedBarcodes> {
+    py.allow_threads(|| dflag_inclusion_persistence(&dflag_cells, &rph_cells, dimension))
+        .map_err(PyValueError::new_err)
+}
+
+/// The image, kernel and cokernel persistence of the map on homology in `dimension` induced by a filtration-preserving
+/// chain map, given by the image of each domain cell as indices into `codomain_cells`, as from `compute_rph_map`.
+#[pyfunction]
+#[pyo3(name = "chain_map_persistence")]
+#[pyo3(signature = (domain_cells, codomain_cells, chain_map, dimension = 1))]
+pub fn chain_map_persistence_py(
+    py: Python<'_>,
+    domain_cells: Vec<GrpphatiRsColumn>,
+    codomain_cells: Vec<GrpphatiRsColumn>,
+    chain_map: Vec<Vec<usize>>,
+    dimension: usize,
+) -> PyResult<InducedBarcodes> {
+    // Repeated indices cancel over Z/2
+    let chain_map: Vec<Chain> = chain_map
+        .into_iter()
+        .map(|image| {
+            image
+                .into_iter()
+                .sorted()
+                .dedup_with_count()
+                .filter(|(count, _)| count % 2 == 1)
+                .map(|(_, idx)| idx)
+                .collect()
+        })
+        .collect();
+    py.allow_threads(|| {
+        chain_map_persistence(&domain_cells, &codomain_cells, &chain_map, dimension)
+    })
+    .map_err(PyValueError::new_err)
+}
+
+/// As [`chain_map_persistence`], for the inclusion of the directed flag complex into the regular path complex.
+pub fn dflag_inclusion_persistence(
+    dflag_cells: &[GrpphatiRsColumn],
+    rph_cells: &[GrpphatiRsColumn],
+    dimension: usize,
+) -> Result<InducedBarcodes, String> {
+    let chain_map = compute_dflag_inclusion(dflag_cells, rph_cells).map_err(|errors| {
+        format!(
+            "Cells do not include into the regular path complex:\n{}",
+            errors.iter().join("\n")
+        )
+    })?;
+    chain_map_persistence(dflag_cells, rph_cells, &chain_map, dimension)
+}
+
+// A chain over Z/2, as sorted indices
+type Chain = Vec<usize>;
+
+// A chain together with the time it enters
+type TimedChain = (FiltrationTime, Chain);
+
+// Addition over Z/2
+fn add_chains(a: &[usize], b: &[usize]) -> Chain {
+    let mut sum = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            Ordering::Less => {
+                sum.push(a[i]);
+                i += 1;
+            }
+            Ordering::Greater => {
+                sum.push(b[j]);
+                j += 1;
+            }
+            Ordering::Equal => {
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    sum.extend_from_slice(&a[i..]);
+    sum.extend_from_slice(&b[j..]);
+    sum
+}
+
+fn by_time<T>(a: &(FiltrationTime, T), b: &(FiltrationTime, T)) -> Ordering {
+    a.0.partial_cmp(&b.0).expect("Times should not be NaN")
+}
+
+/// Column reduction over Z/2, where each column carries a payload chain which is added alongside it,
+/// as V is in R = DV.
+#[derive(Default)]
+struct Reduction {
+    columns: Vec<Chain>,
+    payloads: Vec<Chain>,
+    // Column with each lowest entry
+    pivots: HashMap<usize, usize>,
+}
+
+impl Reduction {
+    // Reduces `column` against the columns so far and appends it, returning its payload if it reduced to zero
+    fn push(&mut self, mut column: Chain, mut payload: Chain) -> Option<Chain> {
+        while let Some(&other) = column.last().and_then(|low| self.pivots.get(low)) {
+            column = add_chains(&column, &self.columns[other]);
+            payload = add_chains(&payload, &self.payloads[other]);
+        }
+        let zero = match column.last() {
+            Some(&low) => {
+                self.pivots.insert(low, self.columns.len());
+                None
+            }
+            None => Some(payload.clone()),
+        };
+        self.columns.push(column);
+        self.payloads.push(payload);
+        zero
+    }
+
+    // The sum of the payloads of the columns adding up to `chain`, which must lie in their span
+    fn express(&self, mut chain: Chain) -> Chain {
+        let mut payload = vec![];
+        while let Some(&low) = chain.last() {
+            let idx = *self
+                .pivots
+                .get(&low)
+                .expect("Chain should lie in the span of the columns");
+            chain = add_chains(&chain, &self.columns[idx]);
+            payload = add_chains(&payload, &self.payloads[idx]);
+        }
+        payload
+    }
+}
+
+// The payloads of the columns which reduce to zero, in order of time, i.e. a filtered basis of the kernel
+fn filtered_kernel(mut columns: Vec<(FiltrationTime, (Chain, Chain))>) -> Vec<TimedChain> {
+    columns.sort_by(by_time);
+    let mut reduction = Reduction::default();
+    columns
+        .into_iter()
+        .filter_map(|(time, (column, payload))| {
+            reduction
+                .push(column, payload)
+                .map(|payload| (time, payload))
+        })
+        .collect()
+}
+
+// A filtered basis of the span of the chains, keeping each which is independent of those entering before it
+fn filtered_basis(mut chains: Vec<TimedChain>) -> Vec<TimedChain> {
+    chains.sort_by(by_time);
+    let mut reduction = Reduction::default();
+    chains
+        .into_iter()
+        .filter(|(_, chain)| reduction.push(chain.clone(), vec![]).is_none())
+        .collect()
+}
+
+/// The barcode of the quotient of the span of `basis`, a filtered basis sorted by time,
+/// by the span of `relations`, which must lie in it at all times.
+/// Each relation is written in the basis, then the relations are reduced so that each kills the youngest class left.
+fn quotient_barcode(basis: &[TimedChain], mut relations: Vec<TimedChain>) -> Vec<Bar> {
+    let mut in_basis = Reduction::default();
+    for (idx, (_, chain)) in basis.iter().enumerate() {
+        in_basis.push(chain.clone(), vec![idx]);
+    }
+    relations.sort_by(by_time);
+    let mut killed = Reduction::default();
+    let mut bars = vec![];
+    for (time, chain) in relations {
+        let coordinates = in_basis.express(chain);
+        killed.push(coordinates, vec![]);
+        if let Some(&youngest) = killed.columns.last().and_then(|column| column.last()) {
+            bars.push((basis[youngest].0, time));
+        }
+    }
+    for (idx, (birth, _)) in basis.iter().enumerate() {
+        if !killed.pivots.contains_key(&idx) {
+            bars.push((*birth, FiltrationTime::INFINITY));
+        }
+    }
+    bars.sort_by(|a, b| a.partial_cmp(b).expect("Times should not be NaN"));
+    bars
+}
+
+// The boundary of every cell, checking that each has an entrance time and that its faces come before it
+fn checked_boundaries(cells: &[GrpphatiRsColumn]) -> Result<Vec<Chain>, String> {
+    if let Some(col) = cells
+        .iter()
+        .find(|col| col.entrance_time.is_none_or(FiltrationTime::is_nan))
+    {
+        return Err(format!("Cell {} has no entrance time", col.col_type));
+    }
+    let index = FaceIndex::new(cells);
+    cells
+        .par_iter()
+        .enumerate()
+        .map(|(idx, col)| index.try_boundary(col, idx))
+        .collect()
+}
+
+// Checks that `chain_map` is a filtration-preserving chain map, given the boundaries of every cell
+fn check_chain_map(
+    domain_cells: &[GrpphatiRsColumn],
+    codomain_cells: &[GrpphatiRsColumn],
+    chain_map: &[Chain],
+    domain_boundaries: &[Chain],
+    codomain_boundaries: &[Chain],
+) -> Result<(), String> {
+    if chain_map.len() != domain_cells.len() {
+        return Err(format!(
+            "Given images of {} cells but there are {} domain cells",
+            chain_map.len(),
+            domain_cells.len()
+        ));
+    }
+    for image in chain_map {
+        if let Some(&idx) = image.iter().find(|&&idx| idx >= codomain_cells.len()) {
+            return Err(format!(
+                "Image {idx} is out of range for {} codomain cells",
+                codomain_cells.len()
+            ));
+        }
+        if image.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(format!("Image {image:?} is not sorted without repeats"));
+        }
+    }
+    let errors: Vec<ChainMapError> = domain_cells
+        .par_iter()
+        .zip(chain_map.par_iter())
+        .zip(domain_boundaries.par_iter())
+        .flat_map_iter(|((col, image), boundary)| {
+            let cell_time = col.get_entrance_time();
+            let mut errors = vec![];
+            for &im_idx in image {
+                let im_col = &codomain_cells[im_idx];
+                if im_col.dimension() != col.dimension() {
+                    errors.push(ChainMapError::DimensionMismatch {
+                        cell: col.col_type,
+                        image: im_col.col_type,
+                    });
+                }
+                let image_time = im_col.get_entrance_time();
+                if image_time > cell_time {
+                    errors.push(ChainMapError::FiltrationViolation {
+                        cell: col.col_type,
+                        cell_time,
+                        image: im_col.col_type,
+                        image_time,
+                    });
+                }
+            }
+            let boundary_of_image = image.iter().fold(vec![], |sum, &im_idx| {
+                add_chains(&sum, &codomain_boundaries[im_idx])
+            });
+            let image_of_boundary = boundary
+                .iter()
+                .fold(vec![], |sum, &face| add_chains(&sum, &chain_map[face]));
+            if boundary_of_image != image_of_boundary {
+                let describe = |chain: Chain| {
+                    chain
+                        .into_iter()
+                        .map(|idx| codomain_cells[idx].col_type)
+                        .collect()
+                };
+                errors.push(ChainMapError::NonCommuting {
+                    cell: col.col_type,
+                    boundary_of_image: describe(boundary_of_image),
+                    image_of_boundary: describe(image_of_boundary),
+                });
+            }
+            errors
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Not a filtration-preserving chain map:\n{}",
+            errors.iter().join("\n")
+        ))
+    }
+}
+
+// The boundary of each cell in `dimension` with its entrance time, and the index of the cell
+fn boundaries(
+    cells: &[GrpphatiRsColumn],
+    all_boundaries: &[Chain],
+    dimension: usize,
+) -> Vec<(FiltrationTime, (Chain, usize))> {
+    cells
+        .iter()
+        .zip(all_boundaries)
+        .enumerate()
+        .filter(|(_, (col, _))| col.dimension() == dimension)
+        .map(|(idx, (col, boundary))| (col.get_entrance_time(), (boundary.clone(), idx)))
+        .collect()
+}
+
+// A filtered basis of the cycles in `dimension`, together with the boundaries of the cells one dimension up
+fn cycles_and_boundaries(
+    cells: &[GrpphatiRsColumn],
+    all_boundaries: &[Chain],
+    dimension: usize,
+) -> (Vec<TimedChain>, Vec<TimedChain>) {
+    let cycles = filtered_kernel(
+        boundaries(cells, all_boundaries, dimension)
+            .into_iter()
+            .map(|(time, (boundary, idx))| (time, (boundary, vec![idx])))
+            .collect(),
+    );
+    let boundaries = boundaries(cells, all_boundaries, dimension + 1)
+        .into_iter()
+        .map(|(time, (boundary, _))| (time, boundary))
+        .collect();
+    (cycles, boundaries)
+}
+
+/// The image, kernel and cokernel persistence of the map on homology in `dimension`
+/// induced by a filtration-preserving chain map, given over Z/2 by the sorted image of each domain cell,
+/// as from [`crate::compute_mapping::compute_map`].
+///
+/// With Z and B the cycles and boundaries, and W the domain cycles sent into codomain boundaries,
+/// the image is Z/W and the kernel is W/B of the domain, and the cokernel is Z/(B + f(Z)) of the codomain,
+/// following Cohen-Steiner, Edelsbrunner, Harer and Morozov.
+/// Each barcode is computed from a filtered basis of the numerator, by writing the generators of the denominator in it.
+///
+/// Returns an error if a cell has no entrance time, a face is missing or comes after its cell,
+/// or the map is not a filtration-preserving chain map.
+pub fn chain_map_persistence(
+    domain_cells: &[GrpphatiRsColumn],
+    codomain_cells: &[GrpphatiRsColumn],
+    chain_map: &[Vec<usize>],
+    dimension: usize,
+) -> Result<InducedBarcodes, String> {
+    let domain_faces = checked_boundaries(domain_cells)?;
+    let codomain_faces = checked_boundaries(codomain_cells)?;
+    check_chain_map(
+        domain_cells,
+        codomain_cells,
+        chain_map,
+        &domain_faces,
+        &codomain_faces,
+    )?;
+    let ((domain_cycles, domain_boundaries), (codomain_cycles, codomain_boundaries)) = rayon::join(
+        || cycles_and_boundaries(domain_cells, &domain_faces, dimension),
+        || cycles_and_boundaries(codomain_cells, &codomain_faces, dimension),
+    );
+    let mapped_cycles: Vec<TimedChain> = domain_cycles
+        .iter()
+        .map(|(time, cycle)| {
+            let image = cycle
+                .iter()
+                .fold(vec![], |image, &idx| add_chains(&image, &chain_map[idx]));
+            (*time, image)
+        })
+        .collect();
+    // W is the projection of the kernel of (z, b) ↦ f(z) + b onto the domain cycles
+    let sent_to_boundaries = filtered_basis(
+        filtered_kernel(
+            mapped_cycles
+                .iter()
+                .zip(domain_cycles.iter())
+                .map(|((time, image), (_, cycle))| (*time, (image.clone(), cycle.clone())))
+                .chain(
+                    codomain_boundaries
+                        .iter()
+                        .map(|(time, boundary)| (*time, (boundary.clone(), vec![]))),
+                )
+                .collect(),
+        )
+        .into_iter()
+        .filter(|(_, cycle)| !cycle.is_empty())
+        .collect(),
+    );
+    let codomain_relations = codomain_boundaries
+        .into_iter()
+        .chain(mapped_cycles)
+        .collect();
+    let (image, (kernel, cokernel)) = rayon::join(
+        || quotient_barcode(&domain_cycles, sent_to_boundaries.clone()),
+        || {
+            rayon::join(
+                || quotient_barcode(&sent_to_boundaries, domain_boundaries),
+                || quotient_barcode(&codomain_cycles, codomain_relations),
+            )
+        },
+    );
+    Ok(InducedBarcodes {
+        image,
+        kernel,
+        cokernel,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::columns::ColumnType;
+
+    fn cells(cells: &[(ColumnType, FiltrationTime)]) -> Vec<GrpphatiRsColumn> {
+        cells
+            .iter()
+            .map(|&(col_type, time)| GrpphatiRsColumn {
+                col_type,
+                entrance_time: Some(time),
+            })
+            .collect()
+    }
+
+    // The triangle 0 → 1 → 2 with 0 → 2, and the same with the triangle filled in at time 2
+    fn triangle() -> (Vec<GrpphatiRsColumn>, Vec<GrpphatiRsColumn>) {
+        let boundary = [
+            (ColumnType::Node(0), 0.0),
+            (ColumnType::Node(1), 0.0),
+            (ColumnType::Node(2), 0.0),
+            (ColumnType::Edge(0, 1), 0.0),
+            (ColumnType::Edge(1, 2), 0.0),
+            (ColumnType::Edge(0, 2), 1.0),
+        ];
+        let filled: Vec<_> = boundary
+            .iter()
+            .copied()
+            .chain([(ColumnType::Triangle(0, 1, 2), 2.0)])
+            .collect();
+        (cells(&boundary), cells(&filled))
+    }
+
+    fn identity(n: usize) -> Vec<Chain> {
+        (0..n).map(|idx| vec![idx]).collect()
+    }
+
+    #[test]
+    fn filling_a_cycle_kills_its_image() {
+        let (domain, codomain) = triangle();
+        let barcodes = chain_map_persistence(&domain, &codomain, &identity(6), 1).unwrap();
+        assert_eq!(barcodes.image, vec![(1.0, 2.0)]);
+        assert_eq!(barcodes.kernel, vec![(2.0, FiltrationTime::INFINITY)]);
+        // The codomain cycle is hit by the domain cycle as soon as it is born
+        assert_eq!(barcodes.cokernel, vec![(1.0, 1.0)]);
+    }
+
+    #[test]
+    fn rejects_maps_which_are_not_chain_maps() {
+        let (domain, codomain) = triangle();
+        assert!(chain_map_persistence(&domain, &codomain, &identity(5), 1).is_err());
+        let mut chain_map = identity(6);
+        chain_map[5] = vec![6];
+        assert!(chain_map_persistence(&domain, &codomain, &chain_map, 1).is_err());
+        chain_map[5] = vec![7];
+        assert!(chain_map_persistence(&domain, &codomain, &chain_map, 1).is_err());
+        chain_map[5] = vec![];
+        assert!(chain_map_persistence(&domain, &codomain, &chain_map, 1).is_err());
+        // The edge 0 → 2 enters the domain before its image
+        let mut early = domain.clone();
+        early[5].entrance_time = Some(0.0);
+        let err = chain_map_persistence(&early, &codomain, &identity(6), 1).unwrap_err();
+        assert!(err.contains("enters at 0 but its image"), "{err}");
+    }
+
+    #[test]
+    fn rejects_cells_without_times_or_faces() {
+        let (domain, codomain) = triangle();
+        let mut untimed = codomain.clone();
+        untimed[6].entrance_time = None;
+        assert!(chain_map_persistence(&domain, &untimed, &identity(6), 1).is_err());
+        let err = chain_map_persistence(&domain[1..], &codomain, &identity(5), 1).unwrap_err();
+        assert!(err.contains("is not among the columns"), "{err}");
+    }
+
+    // Images found from a vertex map always have the dimension of their cell, but given indices need not
+    #[test]
+    fn rejects_images_of_another_dimension() {
+        let (domain, codomain) = triangle();
+        let mut chain_map = identity(6);
+        chain_map[3] = vec![0];
+        let err = chain_map_persistence(&domain, &codomain, &chain_map, 1).unwrap_err();
+        assert!(
+            err.contains("Edge(0,1): sent to Node(0) of a different dimension"),
+            "{err}"
+        );
+    }
+}
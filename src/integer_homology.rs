@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+
+use pyo3::{exceptions::PyValueError, prelude::*};
+use rayon::prelude::*;
+
+use crate::{
+    columns::{ColumnType, GrpphatiRsColumn},
+    Coefficient, FiltrationTime,
+};
+
+/// Integral homology of the (non-persistent) chain complex present at a single filtration value.
+#[pyclass(get_all)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntegerHomology {
+    /// The filtration value at which the complex was taken.
+    pub time: FiltrationTime,
+    /// `betti[k]` is the rank of the free part of H_k.
+    pub betti: Vec<usize>,
+    /// `torsion[k]` lists the torsion coefficients of H_k, i.e. H_k ≅ Z^betti[k] ⊕ ⊕ Z/torsion[k][i].
+    pub torsion: Vec<Vec<Coefficient>>,
+}
+
+#[pymethods]
+impl IntegerHomology {
+    fn __repr__(&self) -> String {
+        format!(
+            "IntegerHomology(time={}, betti={:?}, torsion={:?})",
+            self.time, self.betti, self.torsion
+        )
+    }
+}
+
+/// Computes integral homology of the complex spanned by `cols` at each of the given `times`,
+/// via Smith normal forms of the oriented boundary matrices.
+/// Intended for small and medium complexes since the boundary matrices are dense.
+#[pyfunction]
+#[pyo3(name = "compute_integer_homology")]
+pub fn integer_homology_py(
+    cols: Vec<GrpphatiRsColumn>,
+    times: Vec<FiltrationTime>,
+) -> PyResult<Vec<IntegerHomology>> {
+    integer_homology(&cols, &times).map_err(PyValueError::new_err)
+}
+
+pub fn integer_homology(
+    cols: &[GrpphatiRsColumn],
+    times: &[FiltrationTime],
+) -> Result<Vec<IntegerHomology>, String> {
+    if let Some(col) = cols.iter().find(|col| col.entrance_time.is_none()) {
+        return Err(format!("{} does not have an entrance time", col.col_type));
+    }
+    times
+        .par_iter()
+        .map(|&time| integer_homology_at(cols, time))
+        .collect()
+}
+
+fn integer_homology_at(
+    cols: &[GrpphatiRsColumn],
+    time: FiltrationTime,
+) -> Result<IntegerHomology, String> {
+    let present: Vec<_> = cols
+        .iter()
+        .filter(|col| col.get_entrance_time() <= time)
+        .collect();
+    let max_dim = cols.iter().map(|col| col.dimension()).max().unwrap_or(0);
+    // Index the cells of each dimension
+    let mut cells_by_dim: Vec<Vec<&GrpphatiRsColumn>> = vec![vec![]; max_dim + 1];
+    let mut row_index: HashMap<ColumnType, usize> = HashMap::new();
+    for col in present {
+        let cells = &mut cells_by_dim[col.dimension()];
+        row_index.insert(col.col_type, cells.len());
+        cells.push(col);
+    }
+    // ranks[k] and factors[k] describe the boundary map out of dimension k
+    let mut ranks = vec![0; max_dim + 2];
+    let mut factors = vec![vec![]; max_dim + 2];
+    for dim in 1..=max_dim {
+        let n_rows = cells_by_dim[dim - 1].len();
+        let mut matrix = vec![vec![0; cells_by_dim[dim].len()]; n_rows];
+        for (j, col) in cells_by_dim[dim].iter().enumerate() {
+            for (face, coeff) in col.signed_boundary() {
+                let i = *row_index.get(&face.col_type).ok_or_else(|| {
+                    format!(
+                        "{} is present at time {time} but its face {} is not",
+                        col.col_type, face.col_type
+                    )
+                })?;
+                matrix[i][j] += coeff;
+            }
+        }
+        let invariant_factors = smith_invariant_factors(matrix)?;
+        ranks[dim] = invariant_factors.len();
+        factors[dim] = invariant_factors;
+    }
+    let betti = (0..=max_dim)
+        .map(|dim| cells_by_dim[dim].len() - ranks[dim] - ranks[dim + 1])
+        .collect();
+    let torsion = (0..=max_dim)
+        .map(|dim| {
+            factors[dim + 1]
+                .iter()
+                .copied()
+                .filter(|&factor| factor > 1)
+                .collect()
+        })
+        .collect();
+    Ok(IntegerHomology {
+        time,
+        betti,
+        torsion,
+    })
+}
+
+/// Returns the non-zero invariant factors d_1 | d_2 | ... of the Smith normal form of `matrix`,
+/// given as a dense list of rows.
+/// Fails if an intermediate entry overflows.
+pub fn smith_invariant_factors(
+    mut matrix: Vec<Vec<Coefficient>>,
+) -> Result<Vec<Coefficient>, String> {
+    let n_rows = matrix.len();
+    let n_cols = matrix.first().map(|row| row.len()).unwrap_or(0);
+    let mut factors = vec![];
+    for t in 0..n_rows.min(n_cols) {
+        // Move the smallest entry of the remaining submatrix into position
+        let submatrix = (t..n_rows).flat_map(|i| (t..n_cols).map(move |j| (i, j)));
+        let Some((pi, pj)) = smallest_entry(&matrix, submatrix) else {
+            break;
+        };
+        matrix.swap(t, pi);
+        swap_columns(&mut matrix, t, pj);
+        loop {
+            let pivot = matrix[t][t];
+            let mut cleared = true;
+            for i in t + 1..n_rows {
+                let q = matrix[i][t] / pivot;
+                if q != 0 {
+                    add_row_multiple(&mut matrix, i, t, -q)?;
+                }
+                cleared &= matrix[i][t] == 0;
+            }
+            for j in t + 1..n_cols {
+                let q = matrix[t][j] / pivot;
+                if q != 0 {
+                    add_column_multiple(&mut matrix, j, t, -q)?;
+                }
+                cleared &= matrix[t][j] == 0;
+            }
+            if !cleared {
+                // Some remainder is smaller than the pivot, so use it as the new pivot
+                let candidates = (t + 1..n_rows)
+                    .map(|i| (i, t))
+                    .chain((t + 1..n_cols).map(|j| (t, j)));
+                let (pi, pj) =
+                    smallest_entry(&matrix, candidates).expect("A remainder should be non-zero");
+                matrix.swap(t, pi);
+                swap_columns(&mut matrix, t, pj);
+                continue;
+            }
+            // The pivot must divide every remaining entry
+            let not_divisible =
+                (t + 1..n_rows).find(|&i| (t + 1..n_cols).any(|j| matrix[i][j] % pivot != 0));
+            match not_divisible {
+                Some(i) => add_row_multiple(&mut matrix, t, i, 1)?,
+                None => break,
+            }
+        }
+        factors.push(matrix[t][t].abs());
+    }
+    Ok(factors)
+}
+
+fn smallest_entry(
+    matrix: &[Vec<Coefficient>],
+    positions: impl Iterator<Item = (usize, usize)>,
+) -> Option<(usize, usize)> {
+    positions
+        .filter(|&(i, j)| matrix[i][j] != 0)
+        .min_by_key(|&(i, j)| matrix[i][j].abs())
+}
+
+fn swap_columns(matrix: &mut [Vec<Coefficient>], a: usize, b: usize) {
+    if a != b {
+        for row in matrix.iter_mut() {
+            row.swap(a, b);
+        }
+    }
+}
+
+// row[target] += factor * row[source]
+fn add_row_multiple(
+    matrix: &mut [Vec<Coefficient>],
+    target: usize,
+    source: usize,
+    factor: Coefficient,
+) -> Result<(), String> {
+    for j in 0..matrix[target].len() {
+        let entry = matrix[source][j]
+            .checked_mul(factor)
+            .and_then(|product| product.checked_add(matrix[target][j]))
+            .ok_or("Overflow while computing Smith normal form")?;
+        matrix[target][j] = entry;
+    }
+    Ok(())
+}
+
+// column[target] += factor * column[source]
+fn add_column_multiple(
+    matrix: &mut [Vec<Coefficient>],
+    target: usize,
+    source: usize,
+    factor: Coefficient,
+) -> Result<(), String> {
+    for row in matrix.iter_mut() {
+        row[target] = row[source]
+            .checked_mul(factor)
+            .and_then(|product| product.checked_add(row[target]))
+            .ok_or("Overflow while computing Smith normal form")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(col_type: ColumnType) -> GrpphatiRsColumn {
+        GrpphatiRsColumn {
+            col_type,
+            entrance_time: Some(0.0),
+        }
+    }
+
+    #[test]
+    fn invariant_factors_divide() {
+        let factors = smith_invariant_factors(vec![vec![2, 0], vec![0, 3]]).unwrap();
+        assert_eq!(factors, vec![1, 6]);
+        let factors = smith_invariant_factors(vec![vec![2, 4, 4], vec![-6, 6, 12]]).unwrap();
+        assert_eq!(factors, vec![2, 6]);
+        assert!(smith_invariant_factors(vec![vec![0, 0]])
+            .unwrap()
+            .is_empty());
+    }
+
+    // The boundary of the 2-cells of the 6-vertex triangulation of RP², whose H_1 is Z/2
+    #[test]
+    fn torsion_of_projective_plane() {
+        let triangles = [
+            [0, 1, 3],
+            [0, 1, 4],
+            [0, 2, 3],
+            [0, 2, 5],
+            [0, 4, 5],
+            [1, 2, 4],
+            [1, 2, 5],
+            [1, 3, 5],
+            [2, 3, 4],
+            [3, 4, 5],
+        ];
+        let edges: Vec<(usize, usize)> = (0..6)
+            .flat_map(|i| (i + 1..6).map(move |j| (i, j)))
+            .collect();
+        let mut matrix = vec![vec![0; triangles.len()]; edges.len()];
+        for (col, &[i, j, k]) in triangles.iter().enumerate() {
+            for (face, sign) in [((j, k), 1), ((i, k), -1), ((i, j), 1)] {
+                let row = edges.iter().position(|&edge| edge == face).unwrap();
+                matrix[row][col] = sign;
+            }
+        }
+        let factors = smith_invariant_factors(matrix).unwrap();
+        let mut expected = vec![1; 9];
+        expected.push(2);
+        assert_eq!(factors, expected);
+    }
+
+    // A double edge between nodes 0 and 1 fills the cycle 01 + 10 with coefficient 1
+    #[test]
+    fn double_edge_has_no_torsion() {
+        let cols = vec![
+            cell(ColumnType::Node(0)),
+            cell(ColumnType::Node(1)),
+            cell(ColumnType::Edge(0, 1)),
+            cell(ColumnType::Edge(1, 0)),
+            cell(ColumnType::DoubleEdge(0, 1)),
+        ];
+        let homology = integer_homology(&cols, &[0.0]).unwrap();
+        assert_eq!(homology[0].betti, vec![1, 0, 0]);
+        assert!(homology[0].torsion.iter().all(|torsion| torsion.is_empty()));
+    }
+}
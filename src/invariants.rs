@@ -0,0 +1,194 @@
+use lophat::utils::PersistenceDiagram;
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyDict};
+
+use crate::{columns::GrpphatiRsColumn, serialization::diagram_bars, FiltrationTime};
+
+/// A bar [birth, death), with infinite death for essential features.
+pub type Bar = (FiltrationTime, FiltrationTime);
+
+/// The Betti numbers in `dimension` at each of `times`, as a NumPy array,
+/// from the diagram of `sparsify_and_decompose` and the cells it indexes into.
+#[pyfunction]
+#[pyo3(name = "betti_curve")]
+#[pyo3(signature = (cols, diagram, times, dimension = 1))]
+pub fn betti_curve_py(
+    py: Python<'_>,
+    cols: Vec<GrpphatiRsColumn>,
+    diagram: PersistenceDiagram,
+    times: Vec<FiltrationTime>,
+    dimension: usize,
+) -> PyResult<PyObject> {
+    if times.iter().any(|time| time.is_nan()) {
+        return Err(PyValueError::new_err("Times must not be NaN"));
+    }
+    let bars = bars_in_dimension(&cols, &diagram, dimension).map_err(PyValueError::new_err)?;
+    to_numpy(py, betti_curve(&bars, &times), "int64")
+}
+
+/// The rank of H_k(t1) → H_k(t2) in `dimension` for each query (t1, t2), with t1 <= t2, as a NumPy array.
+/// See `betti_curve` for the other arguments.
+#[pyfunction]
+#[pyo3(name = "rank_invariant")]
+#[pyo3(signature = (cols, diagram, queries, dimension = 1))]
+pub fn rank_invariant_py(
+    py: Python<'_>,
+    cols: Vec<GrpphatiRsColumn>,
+    diagram: PersistenceDiagram,
+    queries: Vec<(FiltrationTime, FiltrationTime)>,
+    dimension: usize,
+) -> PyResult<PyObject> {
+    let bars = bars_in_dimension(&cols, &diagram, dimension).map_err(PyValueError::new_err)?;
+    let ranks = rank_invariant(&bars, &queries).map_err(PyValueError::new_err)?;
+    to_numpy(py, ranks, "int64")
+}
+
+// Converts nested lists of values into a NumPy array of the given dtype
+pub(crate) fn to_numpy(
+    py: Python<'_>,
+    values: impl IntoPy<PyObject>,
+    dtype: &str,
+) -> PyResult<PyObject> {
+    let numpy = py.import("numpy")?;
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("dtype", dtype)?;
+    Ok(numpy
+        .call_method("array", (values.into_py(py),), Some(kwargs))?
+        .into_py(py))
+}
+
+/// The bars of `diagram` in `dimension`, including those of length zero.
+pub fn bars_in_dimension(
+    cols: &[GrpphatiRsColumn],
+    diagram: &PersistenceDiagram,
+    dimension: usize,
+) -> Result<Vec<Bar>, String> {
+    Ok(diagram_bars(cols, diagram, None)?
+        .into_iter()
+        .filter(|bar| bar.dimension == dimension)
+        .map(|bar| (bar.birth, bar.death.unwrap_or(FiltrationTime::INFINITY)))
+        .collect())
+}
+
+/// The number of bars alive at each time, i.e. with birth <= t < death.
+pub fn betti_curve(bars: &[Bar], times: &[FiltrationTime]) -> Vec<usize> {
+    let mut births: Vec<_> = bars.iter().map(|bar| bar.0).collect();
+    let mut deaths: Vec<_> = bars.iter().map(|bar| bar.1).collect();
+    births.sort_by(|a, b| a.partial_cmp(b).expect("Times should not be NaN"));
+    deaths.sort_by(|a, b| a.partial_cmp(b).expect("Times should not be NaN"));
+    // Every bar that has died by t was born by t
+    times
+        .iter()
+        .map(|&t| {
+            births.partition_point(|&birth| birth <= t)
+                - deaths.partition_point(|&death| death <= t)
+        })
+        .collect()
+}
+
+/// The rank of H_k(t1) → H_k(t2) for each query (t1, t2), i.e. the number of bars with birth <= t1 and death > t2.
+/// Queries are answered offline in order of t1, counting deaths with a Fenwick tree.
+pub fn rank_invariant(
+    bars: &[Bar],
+    queries: &[(FiltrationTime, FiltrationTime)],
+) -> Result<Vec<usize>, String> {
+    for &(t1, t2) in queries {
+        if t1.is_nan() || t2.is_nan() || t1 > t2 {
+            return Err(format!("Invalid query ({t1}, {t2}), expected t1 <= t2"));
+        }
+    }
+    let mut by_birth = bars.to_vec();
+    by_birth.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("Times should not be NaN"));
+    let mut deaths: Vec<_> = bars.iter().map(|bar| bar.1).collect();
+    deaths.sort_by(|a, b| a.partial_cmp(b).expect("Times should not be NaN"));
+    deaths.dedup();
+    let mut order: Vec<usize> = (0..queries.len()).collect();
+    order.sort_by(|&a, &b| queries[a].0.partial_cmp(&queries[b].0).unwrap());
+    // tree counts the deaths of the bars born so far, indexed by their rank among the distinct deaths
+    let mut tree = vec![0; deaths.len() + 1];
+    let mut born = 0;
+    let mut ranks = vec![0; queries.len()];
+    for query in order {
+        let (t1, t2) = queries[query];
+        while born < by_birth.len() && by_birth[born].0 <= t1 {
+            let rank = deaths.partition_point(|&death| death < by_birth[born].1);
+            let mut idx = rank + 1;
+            while idx < tree.len() {
+                tree[idx] += 1;
+                idx += idx & idx.wrapping_neg();
+            }
+            born += 1;
+        }
+        // Bars born by t1 which have died by t2
+        let mut dead = 0;
+        let mut idx = deaths.partition_point(|&death| death <= t2);
+        while idx > 0 {
+            dead += tree[idx];
+            idx -= idx & idx.wrapping_neg();
+        }
+        ranks[query] = born - dead;
+    }
+    Ok(ranks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INF: FiltrationTime = FiltrationTime::INFINITY;
+
+    fn bars() -> Vec<Bar> {
+        vec![(0.0, 2.0), (1.0, INF), (1.0, 3.0), (2.0, 2.0)]
+    }
+
+    fn brute_force_rank(bars: &[Bar], (t1, t2): (FiltrationTime, FiltrationTime)) -> usize {
+        bars.iter()
+            .filter(|&&(birth, death)| birth <= t1 && death > t2)
+            .count()
+    }
+
+    #[test]
+    fn counts_bars_alive_at_each_time() {
+        assert_eq!(
+            betti_curve(&bars(), &[-1.0, 0.0, 1.0, 2.0, 3.0, INF]),
+            vec![0, 1, 3, 2, 1, 0]
+        );
+    }
+
+    // Queries at the births and deaths of bars, including ties between them, in no particular order
+    #[test]
+    fn rank_invariant_handles_tied_times() {
+        let bars = bars();
+        let queries = vec![
+            (2.0, 2.0),
+            (0.0, 0.0),
+            (1.0, 2.0),
+            (3.0, 5.0),
+            (1.0, 3.0),
+            (0.0, INF),
+            (2.0, 3.0),
+        ];
+        let ranks = rank_invariant(&bars, &queries).unwrap();
+        assert_eq!(ranks, vec![2, 1, 2, 1, 1, 0, 1]);
+        for (query, rank) in queries.into_iter().zip(ranks) {
+            assert_eq!(rank, brute_force_rank(&bars, query));
+        }
+    }
+
+    #[test]
+    fn rank_at_a_single_time_is_the_betti_number() {
+        let bars = bars();
+        let times = [0.0, 1.0, 2.0, 3.0];
+        let queries: Vec<_> = times.iter().map(|&t| (t, t)).collect();
+        assert_eq!(
+            rank_invariant(&bars, &queries).unwrap(),
+            betti_curve(&bars, &times)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_queries() {
+        assert!(rank_invariant(&bars(), &[(2.0, 1.0)]).is_err());
+        assert!(rank_invariant(&bars(), &[(FiltrationTime::NAN, 1.0)]).is_err());
+        assert_eq!(rank_invariant(&[], &[(0.0, 1.0)]).unwrap(), vec![0]);
+    }
+}
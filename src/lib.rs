@@ -1,40 +1,426 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
 use lophat::{
-    algorithms::{LockFreeAlgorithm, RVDecomposition},
-    columns::VecColumn,
+    algorithms::{LockFreeAlgorithm, RVDecomposition, SerialAlgorithm},
+    columns::{Column, VecColumn},
     options::LoPhatOptions,
     utils::{anti_transpose, PersistenceDiagram},
 };
-use pyo3::prelude::*;
+use pyo3::{exceptions::PyValueError, prelude::*};
 
-mod columns;
+mod cell_store;
+pub mod coefficients;
+pub mod columns;
 mod compute_mapping;
+mod distances;
+mod file_formats;
+pub mod filtrations;
+mod graph;
 mod homology;
+mod incremental;
+mod induced_persistence;
+mod integer_homology;
+mod invariants;
+mod optimisations;
+pub mod pipelines;
+pub mod readers;
+pub mod serialization;
 mod sparsifiers;
+pub mod truncations;
+mod vectorizations;
 
+use cell_store::{
+    load_cells_py, load_sparse_matrix_py, save_cells_py, save_sparse_matrix_py, StoreHeader,
+};
+use coefficients::{decompose_over_field, PrimeField};
 use columns::GrpphatiRsColumn;
-use compute_mapping::compute_map_py;
-use homology::{get_dflag_two_cells, get_rph_two_cells};
+use compute_mapping::{
+    compute_dflag_inclusion_py, compute_map_py, compute_nrph_map_py, verify_chain_map_py,
+    verify_nrph_chain_map_py,
+};
+use distances::{bottleneck_distance_py, pairwise_distances_py, wasserstein_distance_py};
+use file_formats::{read_boundary_matrix_py, write_boundary_matrix_py};
+use filtrations::build_filtration_py;
+use homology::{get_dflag_two_cells, get_nrph_two_cells, get_one_cells, get_rph_two_cells};
+use incremental::RphComplex;
+use induced_persistence::{
+    chain_map_persistence_py, dflag_inclusion_persistence_py, InducedBarcodes,
+};
+use integer_homology::{integer_homology_py, IntegerHomology};
+use invariants::{betti_curve_py, rank_invariant_py};
+use pipelines::{grpdflh_pipeline, grpph_batch, grpph_pipeline};
+use readers::{read_edge_list_py, read_graphml_py};
+use serialization::{read_cells_py, read_diagram_py, write_cells_py, write_diagram_py};
 use sparsifiers::{RustIteratorSparsifier, RustListSparsifier, RustParallelListSparsifier};
+use vectorizations::{betti_vectors_py, landscapes_py, persistence_images_py, silhouettes_py};
 
-type NodeIndex = u32;
-type FiltrationTime = f64;
+pub type NodeIndex = u32;
+pub type FiltrationTime = f64;
+pub type Coefficient = i64;
+
+/// Receives the progress messages, such as "Sparsified", of the longer computations.
+pub type Progress<'a> = &'a (dyn Fn(&str) + Sync);
+
+/// Prints progress to stdout, as the Python functions do.
+pub fn print_progress(message: &str) {
+    println!("{message}");
+}
+
+/// Discards progress, e.g. when computing many small graphs at once.
+pub fn no_progress(_message: &str) {}
+
+/// The R=DV decomposition algorithms provided by LoPHAT.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    Serial,
+    LockFree,
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "serial" => Ok(Algorithm::Serial),
+            "lock_free" => Ok(Algorithm::LockFree),
+            _ => Err(format!(
+                "Unknown algorithm {name}, expected serial or lock_free"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Algorithm::Serial => write!(f, "serial"),
+            Algorithm::LockFree => write!(f, "lock_free"),
+        }
+    }
+}
+
+/// How a boundary matrix is decomposed, as reported by `sparsify_and_decompose`.
+/// The LoPHAT options are only used over Z/2.
+#[pyclass]
+#[derive(Copy, Clone)]
+pub struct DecompositionOptions {
+    pub prime: Coefficient,
+    pub algorithm: Algorithm,
+    /// Whether the anti-transpose is reduced, i.e. cohomology is computed.
+    pub anti_transpose: bool,
+    pub lophat: LoPhatOptions,
+}
+
+#[pymethods]
+impl DecompositionOptions {
+    #[getter]
+    fn prime(&self) -> Coefficient {
+        self.prime
+    }
+
+    #[getter]
+    fn algorithm(&self) -> String {
+        self.algorithm.to_string()
+    }
+
+    #[getter]
+    fn anti_transpose(&self) -> bool {
+        self.anti_transpose
+    }
+
+    #[getter]
+    fn num_threads(&self) -> usize {
+        self.lophat.num_threads
+    }
+
+    #[getter]
+    fn min_chunk_len(&self) -> usize {
+        self.lophat.min_chunk_len
+    }
+
+    #[getter]
+    fn column_height(&self) -> Option<usize> {
+        self.lophat.column_height
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "DecompositionOptions(prime={}, algorithm='{}', anti_transpose={}, num_threads={}, min_chunk_len={}, column_height={})",
+            self.prime,
+            self.algorithm,
+            py_bool(self.anti_transpose),
+            self.lophat.num_threads,
+            self.lophat.min_chunk_len,
+            self.lophat
+                .column_height
+                .map_or("None".to_string(), |height| height.to_string()),
+        )
+    }
+}
 
+fn py_bool(value: bool) -> &'static str {
+    if value {
+        "True"
+    } else {
+        "False"
+    }
+}
+
+impl DecompositionOptions {
+    /// Checks the options against a matrix with `n_cols` columns, resolving the defaults.
+    /// `algorithm` and `anti_transpose` default to the lock-free reduction of the anti-transpose over Z/2;
+    /// over other fields only the serial reduction of the boundary matrix is available.
+    /// A `num_threads` of 0 is resolved to the size of the current thread pool.
+    pub fn resolve(
+        prime: Coefficient,
+        algorithm: Option<Algorithm>,
+        anti_transpose: Option<bool>,
+        lophat: LoPhatOptions,
+        n_cols: usize,
+    ) -> Result<Self, String> {
+        let algorithm = match (prime, algorithm) {
+            (2, algorithm) => algorithm.unwrap_or(Algorithm::LockFree),
+            (_, None | Some(Algorithm::Serial)) => Algorithm::Serial,
+            (_, Some(algorithm)) => {
+                return Err(format!(
+                    "The {algorithm} algorithm is only available over Z/2"
+                ))
+            }
+        };
+        let anti_transpose = match (prime, anti_transpose) {
+            (2, anti_transpose) => anti_transpose.unwrap_or(true),
+            (_, None | Some(false)) => false,
+            (_, Some(true)) => return Err("The anti-transpose is only available over Z/2".into()),
+        };
+        if prime != 2 && lophat.maintain_v {
+            return Err("maintain_v is only available over Z/2".into());
+        }
+        if lophat.min_chunk_len == 0 {
+            return Err("min_chunk_len must be at least 1".into());
+        }
+        if let Some(height) = lophat.column_height {
+            if height < n_cols {
+                return Err(format!(
+                    "column_height {height} is less than the number of columns {n_cols}"
+                ));
+            }
+        }
+        let num_threads = match (algorithm, lophat.num_threads) {
+            (Algorithm::Serial, 0 | 1) => 1,
+            (Algorithm::Serial, n) => {
+                return Err(format!("The serial algorithm cannot use {n} threads"))
+            }
+            (Algorithm::LockFree, 0) => rayon::current_num_threads(),
+            (Algorithm::LockFree, n) => n,
+        };
+        Ok(Self {
+            prime,
+            algorithm,
+            anti_transpose,
+            lophat: LoPhatOptions {
+                num_threads,
+                ..lophat
+            },
+        })
+    }
+}
+
+/// Sparsifies and decomposes the columns, which must be sorted by dimension then entrance time.
+/// Over Z/2 (the default) the anti-transposed matrix is reduced with LoPHAT's lock-free algorithm,
+/// otherwise the oriented boundary matrix is reduced serially over Z/`prime`.
+/// `num_threads` (0 for one thread per CPU), `min_chunk_len` and `column_height` are passed to LoPHAT,
+/// so are only accepted over Z/2.
+/// With `return_options=True`, returns `(diagram, options)` where `options` are the `DecompositionOptions` used.
 #[pyfunction]
-pub fn sparsify_and_decompose(cols: Vec<GrpphatiRsColumn>) -> PersistenceDiagram {
+#[pyo3(signature = (
+    cols,
+    prime = 2,
+    num_threads = None,
+    min_chunk_len = None,
+    column_height = None,
+    algorithm = None,
+    anti_transpose = None,
+    return_options = false,
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn sparsify_and_decompose(
+    py: Python<'_>,
+    cols: Vec<GrpphatiRsColumn>,
+    prime: Coefficient,
+    num_threads: Option<usize>,
+    min_chunk_len: Option<usize>,
+    column_height: Option<usize>,
+    algorithm: Option<&str>,
+    anti_transpose: Option<bool>,
+    return_options: bool,
+) -> PyResult<PyObject> {
+    let field = PrimeField::new(prime).map_err(PyValueError::new_err)?;
+    let options = parse_decomposition_options(
+        cols.len(),
+        prime,
+        num_threads,
+        min_chunk_len,
+        column_height,
+        algorithm,
+        anti_transpose,
+    )
+    .map_err(PyValueError::new_err)?;
+    let diagram = decompose_columns_using(&cols, field, options, &print_progress);
+    if return_options {
+        Ok((diagram, options).into_py(py))
+    } else {
+        Ok(diagram.into_py(py))
+    }
+}
+
+// The options of `sparsify_and_decompose`, where the LoPHAT options must not be given over other fields than Z/2
+fn parse_decomposition_options(
+    n_cols: usize,
+    prime: Coefficient,
+    num_threads: Option<usize>,
+    min_chunk_len: Option<usize>,
+    column_height: Option<usize>,
+    algorithm: Option<&str>,
+    anti_transpose: Option<bool>,
+) -> Result<DecompositionOptions, String> {
+    PrimeField::new(prime)?;
+    if prime != 2 && (num_threads.is_some() || min_chunk_len.is_some() || column_height.is_some()) {
+        return Err(
+            "num_threads, min_chunk_len and column_height are only used by LoPHAT, over Z/2".into(),
+        );
+    }
+    let algorithm = algorithm.map(str::parse).transpose()?;
+    let lophat = LoPhatOptions {
+        num_threads: num_threads.unwrap_or(0),
+        min_chunk_len: min_chunk_len.unwrap_or(10000),
+        column_height,
+        ..Default::default()
+    };
+    DecompositionOptions::resolve(prime, algorithm, anti_transpose, lophat, n_cols)
+}
+
+pub fn decompose_columns(cols: &[GrpphatiRsColumn], field: PrimeField) -> PersistenceDiagram {
+    let options = LoPhatOptions {
+        min_chunk_len: 10000,
+        ..Default::default()
+    };
+    decompose_columns_with(cols, field, options)
+}
+
+/// As [`decompose_columns`], passing `options` to LoPHAT when working over Z/2.
+pub fn decompose_columns_with(
+    cols: &[GrpphatiRsColumn],
+    field: PrimeField,
+    options: LoPhatOptions,
+) -> PersistenceDiagram {
+    let options = DecompositionOptions {
+        prime: field.characteristic(),
+        algorithm: Algorithm::LockFree,
+        anti_transpose: true,
+        lophat: options,
+    };
+    decompose_columns_using(cols, field, options, &print_progress)
+}
+
+/// As [`decompose_columns`], with the algorithm and orientation given by `options`,
+/// which are only used over Z/2, reporting to `progress`.
+pub fn decompose_columns_using(
+    cols: &[GrpphatiRsColumn],
+    field: PrimeField,
+    options: DecompositionOptions,
+    progress: Progress,
+) -> PersistenceDiagram {
     let mut sparsifier = RustParallelListSparsifier::new(2);
-    let sparse_cols: Vec<_> = sparsifier.sparsify(&cols).map(VecColumn::from).collect();
-    println!("Sparsified");
+    if field.characteristic() != 2 {
+        let sparse_cols: Vec<_> = sparsifier.sparsify_signed(cols, field).collect();
+        progress("Sparsified");
+        let diagram = decompose_over_field(sparse_cols, field);
+        progress("Decomposed");
+        return diagram;
+    }
+    let sparse_cols: Vec<_> = sparsifier.sparsify(cols).map(VecColumn::from).collect();
+    progress("Sparsified");
     let width = sparse_cols.len();
-    let at = anti_transpose(&sparse_cols);
-    println!("Anti-transposed");
-    let mut options = LoPhatOptions::default();
-    options.min_chunk_len = 10000;
-    let decomp = LockFreeAlgorithm::decompose(at.into_iter(), Some(options));
-    println!("Decomposed");
+    if width == 0 {
+        return PersistenceDiagram::default();
+    }
+    let matrix = if options.anti_transpose {
+        let at = anti_transpose(&sparse_cols);
+        progress("Anti-transposed");
+        at
+    } else {
+        sparse_cols
+    };
+    let lophat_options = Some(options.lophat);
+    let diagram = match options.algorithm {
+        Algorithm::Serial => {
+            SerialAlgorithm::decompose(matrix.into_iter(), lophat_options).diagram()
+        }
+        Algorithm::LockFree => {
+            LockFreeAlgorithm::decompose(matrix.into_iter(), lophat_options).diagram()
+        }
+    };
+    progress("Decomposed");
+    if options.anti_transpose {
+        diagram.anti_transpose(width)
+    } else {
+        diagram
+    }
+}
+
+/// Decomposes the columns over Z/2 without anti-transposing, maintaining V so that each feature has a representative.
+/// A pair (birth, death) is represented by the cycle killed at death, i.e. column death of R,
+/// and an unpaired birth by column birth of V.
+/// Representatives are given as indices into `cols`.
+/// The diagram does not depend on `algorithm`, but the lock-free algorithm adds columns in an order
+/// that depends on thread scheduling, so only the serial algorithm gives the same representatives on every run.
+pub fn decompose_with_representatives(
+    cols: &[GrpphatiRsColumn],
+    algorithm: Algorithm,
+    options: LoPhatOptions,
+    progress: Progress,
+) -> (PersistenceDiagram, HashMap<usize, Vec<usize>>) {
+    let mut sparsifier = RustParallelListSparsifier::new(2);
+    let sparse_cols: Vec<_> = sparsifier.sparsify(cols).map(VecColumn::from).collect();
+    progress("Sparsified");
+    let options = LoPhatOptions {
+        maintain_v: true,
+        ..options
+    };
+    let (diagram, representatives) = match algorithm {
+        Algorithm::Serial => {
+            let options = LoPhatOptions {
+                num_threads: 1,
+                ..options
+            };
+            let decomp = SerialAlgorithm::decompose(sparse_cols.into_iter(), Some(options));
+            progress("Decomposed");
+            read_representatives(&decomp)
+        }
+        Algorithm::LockFree => {
+            let decomp = LockFreeAlgorithm::decompose(sparse_cols.into_iter(), Some(options));
+            progress("Decomposed");
+            read_representatives(&decomp)
+        }
+    };
+    progress("Got representatives");
+    (diagram, representatives)
+}
+
+fn read_representatives(
+    decomp: &impl RVDecomposition<VecColumn>,
+) -> (PersistenceDiagram, HashMap<usize, Vec<usize>>) {
     let diagram = decomp.diagram();
-    println!("Got diagram");
-    let diagram = diagram.anti_transpose(width);
-    diagram
+    let mut representatives = HashMap::new();
+    for &(_, death) in diagram.paired.iter() {
+        let rep = decomp.get_r_col(death).entries().collect();
+        representatives.insert(death, rep);
+    }
+    for &birth in diagram.unpaired.iter() {
+        let v_col = decomp.get_v_col(birth).expect("V should be maintained");
+        representatives.insert(birth, v_col.entries().collect());
+    }
+    (diagram, representatives)
 }
 
 // TODO: Provide python method which orchestrates entire pipeline
@@ -45,12 +431,153 @@ pub fn sparsify_and_decompose(cols: Vec<GrpphatiRsColumn>) -> PersistenceDiagram
 #[pymodule]
 fn grpphati_rs(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(get_rph_two_cells, m)?)?;
+    m.add_function(wrap_pyfunction!(get_nrph_two_cells, m)?)?;
     m.add_function(wrap_pyfunction!(get_dflag_two_cells, m)?)?;
+    m.add_function(wrap_pyfunction!(get_one_cells, m)?)?;
     m.add_function(wrap_pyfunction!(compute_map_py, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_chain_map_py, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_nrph_map_py, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_nrph_chain_map_py, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_dflag_inclusion_py, m)?)?;
+    m.add_function(wrap_pyfunction!(dflag_inclusion_persistence_py, m)?)?;
+    m.add_function(wrap_pyfunction!(chain_map_persistence_py, m)?)?;
     m.add_function(wrap_pyfunction!(sparsify_and_decompose, m)?)?;
+    m.add_function(wrap_pyfunction!(integer_homology_py, m)?)?;
+    m.add_function(wrap_pyfunction!(build_filtration_py, m)?)?;
+    m.add_function(wrap_pyfunction!(grpph_pipeline, m)?)?;
+    m.add_function(wrap_pyfunction!(grpdflh_pipeline, m)?)?;
+    m.add_function(wrap_pyfunction!(grpph_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(write_boundary_matrix_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_boundary_matrix_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_edge_list_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_graphml_py, m)?)?;
+    m.add_function(wrap_pyfunction!(write_cells_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_cells_py, m)?)?;
+    m.add_function(wrap_pyfunction!(write_diagram_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_diagram_py, m)?)?;
+    m.add_function(wrap_pyfunction!(betti_curve_py, m)?)?;
+    m.add_function(wrap_pyfunction!(rank_invariant_py, m)?)?;
+    m.add_function(wrap_pyfunction!(landscapes_py, m)?)?;
+    m.add_function(wrap_pyfunction!(silhouettes_py, m)?)?;
+    m.add_function(wrap_pyfunction!(persistence_images_py, m)?)?;
+    m.add_function(wrap_pyfunction!(betti_vectors_py, m)?)?;
+    m.add_function(wrap_pyfunction!(bottleneck_distance_py, m)?)?;
+    m.add_function(wrap_pyfunction!(wasserstein_distance_py, m)?)?;
+    m.add_function(wrap_pyfunction!(pairwise_distances_py, m)?)?;
+    m.add_function(wrap_pyfunction!(save_cells_py, m)?)?;
+    m.add_function(wrap_pyfunction!(load_cells_py, m)?)?;
+    m.add_function(wrap_pyfunction!(save_sparse_matrix_py, m)?)?;
+    m.add_function(wrap_pyfunction!(load_sparse_matrix_py, m)?)?;
     m.add_class::<GrpphatiRsColumn>()?;
+    m.add_class::<DecompositionOptions>()?;
+    m.add_class::<StoreHeader>()?;
+    m.add_class::<InducedBarcodes>()?;
+    m.add_class::<IntegerHomology>()?;
+    m.add_class::<RphComplex>()?;
     m.add_class::<RustListSparsifier>()?;
     m.add_class::<RustParallelListSparsifier>()?;
     m.add_class::<RustIteratorSparsifier>()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(
+        prime: Coefficient,
+        algorithm: Option<&str>,
+    ) -> Result<DecompositionOptions, String> {
+        parse_decomposition_options(10, prime, None, None, None, algorithm, None)
+    }
+
+    #[test]
+    fn resolves_defaults() {
+        let options = resolve(2, None).unwrap();
+        assert_eq!(options.algorithm, Algorithm::LockFree);
+        assert!(options.anti_transpose);
+        assert_eq!(options.lophat.num_threads, rayon::current_num_threads());
+        let options = resolve(3, None).unwrap();
+        assert_eq!(options.algorithm, Algorithm::Serial);
+        assert!(!options.anti_transpose);
+        assert_eq!(options.lophat.num_threads, 1);
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        assert!(resolve(4, None).is_err());
+        assert!(resolve(3, Some("lock_free")).is_err());
+        assert!(resolve(2, Some("parallel")).is_err());
+        let parse =
+            |prime, num_threads, min_chunk_len, column_height, algorithm, anti_transpose| {
+                parse_decomposition_options(
+                    10,
+                    prime,
+                    num_threads,
+                    min_chunk_len,
+                    column_height,
+                    algorithm,
+                    anti_transpose,
+                )
+            };
+        assert!(parse(2, Some(2), None, None, Some("serial"), None).is_err());
+        assert!(parse(2, None, Some(0), None, None, None).is_err());
+        assert!(parse(2, None, None, Some(5), None, None).is_err());
+        assert!(parse(3, None, None, None, None, Some(true)).is_err());
+    }
+
+    // LoPHAT is not used over other fields, so its options are rejected rather than ignored
+    #[test]
+    fn rejects_lophat_options_over_other_fields() {
+        let parse = |num_threads, min_chunk_len, column_height| {
+            parse_decomposition_options(
+                10,
+                3,
+                num_threads,
+                min_chunk_len,
+                column_height,
+                None,
+                None,
+            )
+        };
+        assert!(parse(None, None, None).is_ok());
+        assert!(parse(Some(1), None, None).is_err());
+        assert!(parse(None, Some(100), None).is_err());
+        assert!(parse(None, None, Some(20)).is_err());
+        let options =
+            parse_decomposition_options(10, 2, Some(3), Some(100), Some(20), None, None).unwrap();
+        assert_eq!(options.lophat.num_threads, 3);
+        assert_eq!(options.lophat.min_chunk_len, 100);
+        assert_eq!(options.lophat.column_height, Some(20));
+    }
+
+    // The directed 3-cycle 0 → 1 → 2 → 0, whose essential class in dimension 1 is born by the last edge
+    #[test]
+    fn serial_representatives_match_lock_free_diagram() {
+        use columns::ColumnType;
+        let cols: Vec<_> = [
+            ColumnType::Node(0),
+            ColumnType::Node(1),
+            ColumnType::Node(2),
+            ColumnType::Edge(0, 1),
+            ColumnType::Edge(1, 2),
+            ColumnType::Edge(2, 0),
+        ]
+        .into_iter()
+        .map(|col_type| GrpphatiRsColumn {
+            col_type,
+            entrance_time: Some(0.0),
+        })
+        .collect();
+        let options = LoPhatOptions::default();
+        let (serial, reps) =
+            decompose_with_representatives(&cols, Algorithm::Serial, options, &no_progress);
+        let (lock_free, _) =
+            decompose_with_representatives(&cols, Algorithm::LockFree, options, &no_progress);
+        assert_eq!(serial, lock_free);
+        assert!(serial.unpaired.contains(&5));
+        let mut cycle = reps[&5].clone();
+        cycle.sort();
+        assert_eq!(cycle, vec![3, 4, 5]);
+    }
+}
@@ -0,0 +1,479 @@
+use std::collections::{HashMap, HashSet};
+
+use lophat::utils::PersistenceDiagram;
+
+use crate::{
+    columns::{ColumnType, GrpphatiRsColumn},
+    graph::WeightedDigraph,
+    FiltrationTime, NodeIndex,
+};
+
+/// A piece of a graph, with the index of the weakly connected component containing it.
+pub struct GraphPart {
+    pub component: usize,
+    pub graph: WeightedDigraph,
+}
+
+/// Splits `graph` into its weakly connected components.
+/// The complexes of distinct components are disjoint, so their persistent homology is the direct sum.
+pub fn connected_components(graph: &WeightedDigraph) -> Vec<GraphPart> {
+    let (dense, adjacency) = undirected_adjacency(graph);
+    const UNSEEN: usize = usize::MAX;
+    let mut component_of = vec![UNSEEN; graph.nodes.len()];
+    let mut parts = vec![];
+    for root in 0..graph.nodes.len() {
+        if component_of[root] != UNSEEN {
+            continue;
+        }
+        let component = parts.len();
+        component_of[root] = component;
+        let mut nodes = vec![graph.nodes[root]];
+        let mut stack = vec![root];
+        while let Some(v) = stack.pop() {
+            for &w in adjacency[v].iter() {
+                if component_of[w] == UNSEEN {
+                    component_of[w] = component;
+                    nodes.push(graph.nodes[w]);
+                    stack.push(w);
+                }
+            }
+        }
+        parts.push(GraphPart {
+            component,
+            graph: WeightedDigraph {
+                nodes,
+                edges: vec![],
+            },
+        });
+    }
+    for &edge in graph.edges.iter() {
+        parts[component_of[dense[&edge.0]]].graph.edges.push(edge);
+    }
+    parts
+}
+
+/// Splits `graph` into the blocks (biconnected components) of its underlying undirected graph.
+/// Every edge lies in exactly one block and isolated nodes form blocks of their own.
+/// By the wedge decomposition theorem, grounded persistent path homology in dimension 1
+/// is the direct sum over blocks.
+/// This does not hold for the directed flag complex, in which a double edge may be filled by triangles
+/// through a vertex in another block, e.g. the double edge 0 ⇄ 1 by (0, 1, 2) and (1, 0, 2) given the edge 1 → 2.
+pub fn wedge_decomposition(graph: &WeightedDigraph) -> Vec<GraphPart> {
+    let (dense, adjacency) = undirected_adjacency(graph);
+    let n = graph.nodes.len();
+    const UNSEEN: usize = usize::MAX;
+    let mut discovery = vec![UNSEEN; n];
+    let mut low = vec![0; n];
+    let mut time = 0;
+    // Undirected edges (min, max) assigned to their block
+    let mut edge_block: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut block_nodes: Vec<Vec<usize>> = vec![];
+    let mut block_component: Vec<usize> = vec![];
+    let mut n_components = 0;
+    let mut edge_stack: Vec<(usize, usize)> = vec![];
+    for root in 0..n {
+        if discovery[root] != UNSEEN {
+            continue;
+        }
+        let component = n_components;
+        n_components += 1;
+        discovery[root] = time;
+        low[root] = time;
+        time += 1;
+        if adjacency[root].is_empty() {
+            block_nodes.push(vec![root]);
+            block_component.push(component);
+            continue;
+        }
+        // Iterative DFS, each frame holding a node, its parent and the next neighbour to visit
+        let mut frames = vec![(root, UNSEEN, 0)];
+        while let Some(frame) = frames.last_mut() {
+            let (v, parent, next) = *frame;
+            if next < adjacency[v].len() {
+                frame.2 += 1;
+                let w = adjacency[v][next];
+                if discovery[w] == UNSEEN {
+                    edge_stack.push((v, w));
+                    discovery[w] = time;
+                    low[w] = time;
+                    time += 1;
+                    frames.push((w, v, 0));
+                } else if w != parent && discovery[w] < discovery[v] {
+                    edge_stack.push((v, w));
+                    low[v] = low[v].min(discovery[w]);
+                }
+                continue;
+            }
+            frames.pop();
+            if parent == UNSEEN {
+                continue;
+            }
+            low[parent] = low[parent].min(low[v]);
+            if low[v] >= discovery[parent] {
+                // parent separates the subtree at v, so the edges above (parent, v) form a block
+                let block = block_nodes.len();
+                let mut nodes = vec![];
+                while let Some((a, b)) = edge_stack.pop() {
+                    edge_block.insert((a.min(b), a.max(b)), block);
+                    nodes.extend([a, b]);
+                    if (a, b) == (parent, v) {
+                        break;
+                    }
+                }
+                nodes.sort_unstable();
+                nodes.dedup();
+                block_nodes.push(nodes);
+                block_component.push(component);
+            }
+        }
+    }
+    let mut block_edges = vec![vec![]; block_nodes.len()];
+    for &(i, j, weight) in graph.edges.iter() {
+        let (a, b) = (dense[&i], dense[&j]);
+        block_edges[edge_block[&(a.min(b), a.max(b))]].push((i, j, weight));
+    }
+    block_nodes
+        .into_iter()
+        .zip(block_edges)
+        .zip(block_component)
+        .map(|((nodes, edges), component)| GraphPart {
+            component,
+            graph: WeightedDigraph {
+                nodes: nodes.into_iter().map(|node| graph.nodes[node]).collect(),
+                edges,
+            },
+        })
+        .collect()
+}
+
+// Dense indices of the nodes, and the deduplicated neighbours of each node in the underlying undirected graph
+fn undirected_adjacency(graph: &WeightedDigraph) -> (HashMap<NodeIndex, usize>, Vec<Vec<usize>>) {
+    let dense: HashMap<NodeIndex, usize> = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(idx, &node)| (node, idx))
+        .collect();
+    let mut adjacency = vec![vec![]; graph.nodes.len()];
+    for &(i, j, _) in graph.edges.iter() {
+        let (a, b) = (dense[&i], dense[&j]);
+        adjacency[a].push(b);
+        adjacency[b].push(a);
+    }
+    for neighbours in adjacency.iter_mut() {
+        neighbours.sort_unstable();
+        neighbours.dedup();
+    }
+    (dense, adjacency)
+}
+
+/// What [`prune_appendages`] removed from a graph.
+#[derive(Clone, Debug, Default)]
+pub struct PrunedAppendages {
+    /// Leaves in the order they were removed, each with the edge joining it to the rest of the graph.
+    pub leaves: Vec<(NodeIndex, (NodeIndex, NodeIndex, FiltrationTime))>,
+    /// Nodes left without any edges, each of which carries an essential class in dimension 0.
+    pub isolated: Vec<NodeIndex>,
+}
+
+impl PrunedAppendages {
+    /// Adds a node cell and an essential class in dimension 0 for each isolated node,
+    /// restoring the diagram of the pruned graph to that of the original.
+    /// The removed leaves only carry zero-length bars, so there is nothing to restore for them.
+    pub fn restore_isolated(
+        &self,
+        cols: &mut Vec<GrpphatiRsColumn>,
+        diagram: &mut PersistenceDiagram,
+    ) {
+        for &node in self.isolated.iter() {
+            diagram.unpaired.insert(cols.len());
+            cols.push(GrpphatiRsColumn {
+                col_type: ColumnType::Node(node),
+                entrance_time: Some(0.0),
+            });
+        }
+    }
+}
+
+/// Iteratively removes nodes without edges and leaves joined to the rest of the graph by a single edge,
+/// which prunes dangling in-trees and out-trees.
+/// Such a leaf and its edge form a block which is contractible in every filtration,
+/// so by the wedge decomposition theorem removing it leaves grounded persistent homology in dimension 1 unchanged.
+/// Leaves joined by a double edge are kept, since the double edge carries a bar.
+/// As with [`wedge_decomposition`], this does not hold for the directed flag complex,
+/// since the triangles filling a double edge may pass through a pruned leaf.
+pub fn prune_appendages(graph: &WeightedDigraph) -> (WeightedDigraph, PrunedAppendages) {
+    let (dense, adjacency) = undirected_adjacency(graph);
+    // Indices of the edges between each pair of neighbours (min, max)
+    let mut between: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (idx, &(i, j, _)) in graph.edges.iter().enumerate() {
+        let (a, b) = (dense[&i], dense[&j]);
+        between.entry((a.min(b), a.max(b))).or_default().push(idx);
+    }
+    let single_direction = |a: usize, b: usize| {
+        let edges = &between[&(a.min(b), a.max(b))];
+        edges
+            .iter()
+            .all(|&idx| graph.edges[idx].0 == graph.edges[edges[0]].0)
+    };
+    let mut degree: Vec<usize> = adjacency
+        .iter()
+        .map(|neighbours| neighbours.len())
+        .collect();
+    let mut removed = vec![false; graph.nodes.len()];
+    let mut pruned = PrunedAppendages::default();
+    let mut queue: Vec<usize> = (0..graph.nodes.len()).filter(|&v| degree[v] <= 1).collect();
+    while let Some(v) = queue.pop() {
+        if removed[v] {
+            continue;
+        }
+        match degree[v] {
+            0 => {
+                removed[v] = true;
+                pruned.isolated.push(graph.nodes[v]);
+            }
+            1 => {
+                let u = *adjacency[v]
+                    .iter()
+                    .find(|&&u| !removed[u])
+                    .expect("Leaf should have a neighbour");
+                if !single_direction(u, v) {
+                    continue;
+                }
+                removed[v] = true;
+                // Keep the cheapest of any parallel edges, as in the filtration
+                let edge = between[&(u.min(v), u.max(v))]
+                    .iter()
+                    .map(|&idx| graph.edges[idx])
+                    .min_by(|a, b| a.2.partial_cmp(&b.2).expect("Weights should not be NaN"))
+                    .unwrap();
+                pruned.leaves.push((graph.nodes[v], edge));
+                degree[u] -= 1;
+                if degree[u] <= 1 {
+                    queue.push(u);
+                }
+            }
+            _ => {}
+        }
+    }
+    let kept: HashSet<NodeIndex> = graph
+        .nodes
+        .iter()
+        .zip(removed.iter())
+        .filter(|(_, &removed)| !removed)
+        .map(|(&node, _)| node)
+        .collect();
+    let core = WeightedDigraph {
+        nodes: graph
+            .nodes
+            .iter()
+            .copied()
+            .filter(|node| kept.contains(node))
+            .collect(),
+        edges: graph
+            .edges
+            .iter()
+            .copied()
+            .filter(|(i, j, _)| kept.contains(i) && kept.contains(j))
+            .collect(),
+    };
+    (core, pruned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        filtrations::FiltrationKind,
+        no_progress,
+        pipelines::{grounded_ph_by_parts, HomologyKind},
+        truncations::Truncation,
+    };
+
+    // The isolated node 6, then the cycles 0 → 1 → 2 → 0 and 0 → 3 → 4 → 0 wedged at 0,
+    // with the edge 4 → 5 hanging off
+    fn wedge_of_cycles() -> WeightedDigraph {
+        let edges = vec![
+            (0, 1, 1.0),
+            (1, 2, 1.0),
+            (2, 0, 1.0),
+            (0, 3, 2.0),
+            (3, 4, 2.0),
+            (4, 0, 2.0),
+            (4, 5, 1.0),
+        ];
+        WeightedDigraph::new(vec![6], edges).unwrap()
+    }
+
+    fn sorted_nodes(part: &GraphPart) -> Vec<NodeIndex> {
+        let mut nodes = part.graph.nodes.clone();
+        nodes.sort_unstable();
+        nodes
+    }
+
+    #[test]
+    fn splits_a_wedge_of_cycles_into_blocks() {
+        let graph = wedge_of_cycles();
+        let mut blocks: Vec<_> = wedge_decomposition(&graph)
+            .iter()
+            .map(|part| (sorted_nodes(part), part.graph.edges.len(), part.component))
+            .collect();
+        blocks.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            blocks,
+            vec![
+                (vec![0, 1, 2], 3, 1),
+                (vec![0, 3, 4], 3, 1),
+                (vec![4, 5], 1, 1),
+                (vec![6], 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_into_components() {
+        let parts = connected_components(&wedge_of_cycles());
+        let nodes: Vec<_> = parts.iter().map(sorted_nodes).collect();
+        assert_eq!(nodes, vec![vec![6], vec![0, 1, 2, 3, 4, 5]]);
+        assert_eq!(parts[1].graph.edges.len(), 7);
+    }
+
+    // The bars of positive length in dimension 1, including essential ones
+    fn one_dimensional_bars(
+        parts: Vec<GraphPart>,
+        homology: HomologyKind,
+    ) -> Vec<(FiltrationTime, FiltrationTime)> {
+        let (cols, diagram) = grounded_ph_by_parts(
+            parts,
+            FiltrationKind::ShortestPath,
+            homology,
+            Truncation::default(),
+            &no_progress,
+        );
+        let time = |idx: usize| cols[idx].get_entrance_time();
+        let mut bars: Vec<_> = diagram
+            .paired
+            .iter()
+            .filter(|&&(birth, _)| cols[birth].dimension() == 1)
+            .map(|&(birth, death)| (time(birth), time(death)))
+            .filter(|(birth, death)| birth < death)
+            .chain(
+                diagram
+                    .unpaired
+                    .iter()
+                    .filter(|&&birth| cols[birth].dimension() == 1)
+                    .map(|&birth| (time(birth), FiltrationTime::INFINITY)),
+            )
+            .collect();
+        bars.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        bars
+    }
+
+    #[test]
+    fn wedge_keeps_one_dimensional_bars() {
+        let graph = wedge_of_cycles();
+        let whole = one_dimensional_bars(connected_components(&graph), HomologyKind::Rph);
+        assert_eq!(whole, vec![(0.0, 2.0), (0.0, 4.0)]);
+        assert_eq!(
+            one_dimensional_bars(wedge_decomposition(&graph), HomologyKind::Rph),
+            whole
+        );
+    }
+
+    // The double edge 0 ⇄ 1 with the leaf edge 1 → 2 hanging off.
+    // In the directed flag complex the triangles (0, 1, 2) and (1, 0, 2) enter at 2 and sum to the double edge,
+    // but they pass through 2, so splitting off or pruning the leaf leaves the double edge essential.
+    // In path homology the double edge is filled by its own 2-cell at 1 either way.
+    #[test]
+    fn wedge_and_pruning_do_not_hold_for_dflag() {
+        let graph =
+            WeightedDigraph::new(vec![], vec![(0, 1, 1.0), (1, 0, 1.0), (1, 2, 1.0)]).unwrap();
+        let (core, pruned) = prune_appendages(&graph);
+        assert_eq!(pruned.leaves.len(), 1);
+        assert_eq!(wedge_decomposition(&graph).len(), 2);
+        for (homology, whole, split) in [
+            (HomologyKind::Rph, (0.0, 1.0), (0.0, 1.0)),
+            (
+                HomologyKind::Dflag,
+                (0.0, 2.0),
+                (0.0, FiltrationTime::INFINITY),
+            ),
+        ] {
+            assert_eq!(
+                one_dimensional_bars(connected_components(&graph), homology),
+                vec![whole]
+            );
+            assert_eq!(
+                one_dimensional_bars(wedge_decomposition(&graph), homology),
+                vec![split]
+            );
+            assert_eq!(
+                one_dimensional_bars(connected_components(&core), homology),
+                vec![split]
+            );
+        }
+    }
+
+    // The cut vertices 0 and 4 each get a single node cell, and every cell appears once in the diagram
+    #[test]
+    fn wedge_merges_shared_nodes() {
+        let graph = wedge_of_cycles();
+        let (cols, diagram) = grounded_ph_by_parts(
+            wedge_decomposition(&graph),
+            FiltrationKind::ShortestPath,
+            HomologyKind::Rph,
+            Truncation::default(),
+            &no_progress,
+        );
+        let mut nodes: Vec<_> = cols
+            .iter()
+            .filter_map(|col| match col.col_type {
+                ColumnType::Node(node) => Some(node),
+                _ => None,
+            })
+            .collect();
+        nodes.sort_unstable();
+        assert_eq!(nodes, vec![0, 1, 2, 3, 4, 5, 6]);
+        let mut in_diagram: Vec<_> = diagram
+            .paired
+            .iter()
+            .flat_map(|&(birth, death)| [birth, death])
+            .chain(diagram.unpaired.iter().copied())
+            .collect();
+        let n_entries = in_diagram.len();
+        in_diagram.sort_unstable();
+        in_diagram.dedup();
+        assert_eq!(in_diagram.len(), n_entries);
+        assert!(in_diagram.iter().all(|&idx| idx < cols.len()));
+        let n_in_dimension = |dimension: usize| {
+            in_diagram
+                .iter()
+                .filter(|&&idx| cols[idx].dimension() == dimension)
+                .count()
+        };
+        // Every node is born once, and the two components each keep one essential class
+        assert_eq!(n_in_dimension(0), 7);
+        let essential = diagram
+            .unpaired
+            .iter()
+            .filter(|&&idx| cols[idx].dimension() == 0)
+            .count();
+        assert_eq!(essential, 2);
+    }
+
+    // The tree 4 → 5 → 7 is pruned, the double edge 2 ⇄ 8 and the cycles are kept
+    #[test]
+    fn prunes_trees_but_not_double_edges() {
+        let mut graph = wedge_of_cycles();
+        graph.edges.extend([(5, 7, 1.0), (2, 8, 1.0), (8, 2, 1.0)]);
+        graph.nodes.extend([7, 8]);
+        let (core, pruned) = prune_appendages(&graph);
+        let mut nodes = core.nodes.clone();
+        nodes.sort_unstable();
+        assert_eq!(nodes, vec![0, 1, 2, 3, 4, 8]);
+        let mut leaves: Vec<_> = pruned.leaves.iter().map(|(leaf, _)| *leaf).collect();
+        leaves.sort_unstable();
+        assert_eq!(leaves, vec![5, 7]);
+        assert_eq!(pruned.isolated, vec![6]);
+    }
+}
@@ -0,0 +1,422 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use lophat::{options::LoPhatOptions, utils::PersistenceDiagram};
+use pyo3::{
+    exceptions::{PyRuntimeError, PyValueError},
+    prelude::*,
+};
+use rayon::{prelude::*, ThreadPoolBuilder};
+
+use crate::{
+    coefficients::PrimeField,
+    columns::{ColumnType, GrpphatiRsColumn},
+    decompose_columns_using,
+    filtrations::{build_filtration, FiltrationKind},
+    graph::{report_self_loops, WeightedDigraph},
+    homology::{build_dflag_two_cells, build_path_two_cells_with, compare_columns, EdgeMap},
+    no_progress,
+    optimisations::{connected_components, prune_appendages, wedge_decomposition, GraphPart},
+    print_progress,
+    truncations::Truncation,
+    Algorithm, DecompositionOptions, FiltrationTime, NodeIndex, Progress,
+};
+
+/// The homology theories with a native pipeline.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HomologyKind {
+    /// Regular path homology (GrPPH).
+    Rph,
+    /// Homology of the directed flag complex (GrPdFlH).
+    Dflag,
+}
+
+impl FromStr for HomologyKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "rph" => Ok(HomologyKind::Rph),
+            "dflag" => Ok(HomologyKind::Dflag),
+            _ => Err(format!("Unknown homology {name}, expected rph or dflag")),
+        }
+    }
+}
+
+/// Computes grounded persistent path homology of the digraph with the given nodes and weighted edges,
+/// building the named filtration, cells and decomposition natively.
+/// Each weakly connected component is computed independently, in parallel.
+/// Returns the cells, grouped by component and sorted by dimension then entrance time within each,
+/// together with the diagram which indexes into them.
+/// With `wedge`, components are further split into blocks at their cut vertices,
+/// and only the diagram in dimensions 0 and 1 is meaningful.
+/// With `prune`, dangling trees and isolated nodes are removed before building the filtration,
+/// and isolated nodes are added back as essential classes in dimension 0.
+/// The cells of the removed trees are omitted, so again only dimensions 0 and 1 are meaningful.
+/// The filtration of each part is truncated once it contains a cone and the part itself with `truncation="cone"`, and at `max_time` if given,
+/// after which only the diagram in dimensions 0 and 1 is meaningful.
+#[pyfunction]
+#[pyo3(signature = (
+    nodes,
+    edges,
+    filtration = "shortest_path",
+    wedge = false,
+    prune = false,
+    truncation = "none",
+    max_time = None,
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn grpph_pipeline(
+    py: Python<'_>,
+    nodes: Vec<NodeIndex>,
+    edges: Vec<(NodeIndex, NodeIndex, FiltrationTime)>,
+    filtration: &str,
+    wedge: bool,
+    prune: bool,
+    truncation: &str,
+    max_time: Option<FiltrationTime>,
+) -> PyResult<(Vec<GrpphatiRsColumn>, PersistenceDiagram)> {
+    let kind: FiltrationKind = filtration.parse().map_err(PyValueError::new_err)?;
+    let truncation = Truncation::new(truncation, max_time).map_err(PyValueError::new_err)?;
+    let (mut graph, skipped) =
+        WeightedDigraph::new_counting_self_loops(nodes, edges).map_err(PyValueError::new_err)?;
+    Ok(py.allow_threads(|| {
+        report_self_loops(skipped, &print_progress);
+        let mut pruned = None;
+        if prune {
+            let (core, removed) = prune_appendages(&graph);
+            print_progress(&format!(
+                "Pruned {} leaves and {} isolated nodes",
+                removed.leaves.len(),
+                removed.isolated.len()
+            ));
+            graph = core;
+            pruned = Some(removed);
+        }
+        let parts = if wedge {
+            wedge_decomposition(&graph)
+        } else {
+            connected_components(&graph)
+        };
+        print_progress(&format!("Split into {} parts", parts.len()));
+        let (mut cols, mut diagram) =
+            grounded_ph_by_parts(parts, kind, HomologyKind::Rph, truncation, &print_progress);
+        if let Some(pruned) = pruned {
+            pruned.restore_isolated(&mut cols, &mut diagram);
+        }
+        (cols, diagram)
+    }))
+}
+
+/// Computes grounded persistent directed flag complex homology (GrPdFlH) of the digraph with the given nodes
+/// and weighted edges, natively, as `grpph_pipeline` does for path homology.
+/// Each weakly connected component is computed independently, in parallel,
+/// and truncated as given by `truncation` and `max_time`.
+/// There are no `wedge` or `prune` options, since neither decomposition holds for the directed flag complex:
+/// triangles through a cut vertex or a leaf may fill a double edge in another block,
+/// as in the counterexample tested in `optimisations`.
+#[pyfunction]
+#[pyo3(signature = (
+    nodes,
+    edges,
+    filtration = "shortest_path",
+    truncation = "none",
+    max_time = None,
+))]
+pub fn grpdflh_pipeline(
+    py: Python<'_>,
+    nodes: Vec<NodeIndex>,
+    edges: Vec<(NodeIndex, NodeIndex, FiltrationTime)>,
+    filtration: &str,
+    truncation: &str,
+    max_time: Option<FiltrationTime>,
+) -> PyResult<(Vec<GrpphatiRsColumn>, PersistenceDiagram)> {
+    let kind: FiltrationKind = filtration.parse().map_err(PyValueError::new_err)?;
+    let truncation = Truncation::new(truncation, max_time).map_err(PyValueError::new_err)?;
+    let (graph, skipped) =
+        WeightedDigraph::new_counting_self_loops(nodes, edges).map_err(PyValueError::new_err)?;
+    Ok(py.allow_threads(|| {
+        report_self_loops(skipped, &print_progress);
+        let parts = connected_components(&graph);
+        print_progress(&format!("Split into {} parts", parts.len()));
+        grounded_ph_by_parts(
+            parts,
+            kind,
+            HomologyKind::Dflag,
+            truncation,
+            &print_progress,
+        )
+    }))
+}
+
+/// Computes grounded persistent path homology for each of a batch of digraphs, given by their weighted edge lists,
+/// in parallel across graphs, each being computed on a single thread without printing progress.
+/// `nodes`, if given, lists the nodes of each graph, so that isolated nodes can be included.
+/// Returns the cells and diagram of each graph, as in `grpph_pipeline`.
+#[pyfunction]
+#[pyo3(signature = (edge_lists, filtration = "shortest_path", nodes = None))]
+pub fn grpph_batch(
+    py: Python<'_>,
+    edge_lists: Vec<Vec<(NodeIndex, NodeIndex, FiltrationTime)>>,
+    filtration: &str,
+    nodes: Option<Vec<Vec<NodeIndex>>>,
+) -> PyResult<Vec<(Vec<GrpphatiRsColumn>, PersistenceDiagram)>> {
+    let kind: FiltrationKind = filtration.parse().map_err(PyValueError::new_err)?;
+    let mut nodes = nodes.unwrap_or_else(|| vec![vec![]; edge_lists.len()]);
+    if nodes.len() != edge_lists.len() {
+        return Err(PyValueError::new_err(format!(
+            "Given nodes for {} graphs but edges for {}",
+            nodes.len(),
+            edge_lists.len()
+        )));
+    }
+    let graphs = edge_lists
+        .into_iter()
+        .zip(nodes.iter_mut())
+        .enumerate()
+        .map(|(idx, (edges, nodes))| {
+            WeightedDigraph::new(std::mem::take(nodes), edges)
+                .map_err(|e| PyValueError::new_err(format!("Graph {idx}: {e}")))
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    let field = PrimeField::new(2).expect("2 is prime");
+    py.allow_threads(|| {
+        map_on_single_threads(&graphs, |graph| {
+            let edge_map = build_filtration(graph, kind);
+            let cols = grounded_cells(graph, &edge_map, HomologyKind::Rph, &no_progress);
+            let options = DecompositionOptions {
+                prime: 2,
+                algorithm: Algorithm::Serial,
+                anti_transpose: true,
+                lophat: LoPhatOptions {
+                    num_threads: 1,
+                    ..Default::default()
+                },
+            };
+            let diagram = decompose_columns_using(&cols, field, options, &no_progress);
+            (cols, diagram)
+        })
+    })
+    .map_err(PyRuntimeError::new_err)
+}
+
+// Maps each item on a single thread, sharing the items out over as many threads as the current pool.
+// Each thread installs its own one-thread pool, so the parallel iterators within `compute`
+// run serially there rather than being split over the other threads
+fn map_on_single_threads<T: Sync, R: Send>(
+    items: &[T],
+    compute: impl Fn(&T) -> R + Sync,
+) -> Result<Vec<R>, String> {
+    let next = AtomicUsize::new(0);
+    let n_threads = rayon::current_num_threads().min(items.len());
+    let computed = thread::scope(|scope| {
+        let workers: Vec<_> = (0..n_threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let pool = ThreadPoolBuilder::new()
+                        .num_threads(1)
+                        .build()
+                        .map_err(|e| e.to_string())?;
+                    Ok(pool.install(|| {
+                        let mut computed = vec![];
+                        loop {
+                            let idx = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(item) = items.get(idx) else {
+                                break computed;
+                            };
+                            computed.push((idx, compute(item)));
+                        }
+                    }))
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("Batch thread panicked"))
+            .collect::<Result<Vec<_>, String>>()
+    })?;
+    let mut results: Vec<Option<R>> = (0..items.len()).map(|_| None).collect();
+    for (idx, result) in computed.into_iter().flatten() {
+        results[idx] = Some(result);
+    }
+    Ok(results
+        .into_iter()
+        .map(|result| result.expect("Every item should be computed"))
+        .collect())
+}
+
+// Parts with fewer cells than this are decomposed on a single thread, since they are processed in parallel
+const PARALLEL_DECOMPOSITION_THRESHOLD: usize = 10000;
+
+/// Computes the grounded cells and diagram of each part in parallel, then concatenates the cells and merges the diagrams.
+/// Each weakly connected component keeps a single essential class in dimension 0,
+/// even if it is split over several parts, and each node a single cell, even if it is in several parts.
+/// The pairs in dimension 0 then give the right bars but need not be a reduction of the merged cells.
+/// The filtration of each part is truncated separately, so a cone in one part does not wait for the others.
+pub fn grounded_ph_by_parts(
+    parts: Vec<GraphPart>,
+    kind: FiltrationKind,
+    homology: HomologyKind,
+    truncation: Truncation,
+    progress: Progress,
+) -> (Vec<GrpphatiRsColumn>, PersistenceDiagram) {
+    let field = PrimeField::new(2).expect("2 is prime");
+    let results: Vec<_> = parts
+        .into_par_iter()
+        .map(|part| {
+            let mut edge_map = build_filtration(&part.graph, kind);
+            truncation.apply(&part.graph, &mut edge_map, homology);
+            let cols = grounded_cells(&part.graph, &edge_map, homology, progress);
+            let options = DecompositionOptions {
+                prime: 2,
+                algorithm: Algorithm::LockFree,
+                anti_transpose: true,
+                lophat: LoPhatOptions {
+                    num_threads: if cols.len() < PARALLEL_DECOMPOSITION_THRESHOLD {
+                        1
+                    } else {
+                        0
+                    },
+                    min_chunk_len: 10000,
+                    ..Default::default()
+                },
+            };
+            let diagram = decompose_columns_using(&cols, field, options, progress);
+            (part.component, cols, diagram)
+        })
+        .collect();
+    let mut all_cols = vec![];
+    let mut merged = PersistenceDiagram::default();
+    let mut seen_nodes = HashSet::new();
+    let mut seen_components = HashSet::new();
+    // Nodes of each component whose essential class was dropped, and deaths whose birth was a repeated node
+    let mut spare_nodes: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut orphaned_deaths = vec![];
+    for (component, cols, diagram) in results {
+        // A cut vertex is a node of each of its blocks, but only its first node cell is kept
+        let mut next = all_cols.len();
+        let index: Vec<_> = cols
+            .iter()
+            .map(|col| match col.col_type {
+                ColumnType::Node(node) if !seen_nodes.insert(node) => None,
+                _ => {
+                    next += 1;
+                    Some(next - 1)
+                }
+            })
+            .collect();
+        for (birth, death) in diagram.paired {
+            let death = index[death].expect("Node cells never die");
+            match index[birth] {
+                Some(birth) => {
+                    merged.paired.insert((birth, death));
+                }
+                None => orphaned_deaths.push((component, death)),
+            }
+        }
+        for birth in diagram.unpaired {
+            // Other parts of the component share its essential class in dimension 0
+            let is_shared = cols[birth].dimension() == 0 && !seen_components.insert(component);
+            match (index[birth], is_shared) {
+                (Some(birth), false) => {
+                    merged.unpaired.insert(birth);
+                }
+                (Some(birth), true) => spare_nodes.entry(component).or_default().push(birth),
+                (None, _) => {}
+            }
+        }
+        all_cols.extend(
+            cols.into_iter()
+                .zip(index)
+                .filter_map(|(col, index)| index.map(|_| col)),
+        );
+    }
+    // Each further part of a component repeats one node and drops one essential class,
+    // so every orphaned death can be paired with a spare node, which also enters at time 0
+    for (component, death) in orphaned_deaths {
+        let birth = spare_nodes
+            .get_mut(&component)
+            .and_then(Vec::pop)
+            .expect("Each repeated node should leave a spare node in its component");
+        merged.paired.insert((birth, death));
+    }
+    (all_cols, merged)
+}
+
+/// Cells of the filtration `edge_map`, grounded at `graph`: nodes and the original edges enter at time 0,
+/// all other edges and 2-cells enter as in the filtration.
+/// Sorted by dimension then entrance time, with ties broken by column type.
+pub fn grounded_cells(
+    graph: &WeightedDigraph,
+    edge_map: &EdgeMap,
+    homology: HomologyKind,
+    progress: Progress,
+) -> Vec<GrpphatiRsColumn> {
+    let mut original_edges = build_filtration(graph, FiltrationKind::Weight);
+    let mut cols: Vec<_> = graph
+        .nodes
+        .iter()
+        .map(|&node| GrpphatiRsColumn {
+            col_type: ColumnType::Node(node),
+            entrance_time: Some(0.0),
+        })
+        .collect();
+    for (&i, targets) in edge_map.iter() {
+        for (&j, &time) in targets.iter() {
+            let is_original = original_edges
+                .get_mut(&i)
+                .is_some_and(|targets| targets.remove(&j).is_some());
+            cols.push(GrpphatiRsColumn {
+                col_type: ColumnType::Edge(i, j),
+                entrance_time: Some(if is_original { 0.0 } else { time }),
+            });
+        }
+    }
+    // Original edges which the filtration dropped, e.g. by truncation
+    for (i, targets) in original_edges {
+        for j in targets.into_keys() {
+            cols.push(GrpphatiRsColumn {
+                col_type: ColumnType::Edge(i, j),
+                entrance_time: Some(0.0),
+            });
+        }
+    }
+    cols.extend(match homology {
+        HomologyKind::Rph => build_path_two_cells_with(edge_map, None, true, progress),
+        HomologyKind::Dflag => build_dflag_two_cells(edge_map, None),
+    });
+    cols.sort_unstable_by(|col_a, col_b| {
+        col_a
+            .dimension()
+            .cmp(&col_b.dimension())
+            .then_with(|| compare_columns(col_a, col_b))
+    });
+    cols
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_each_item_on_a_single_thread() {
+        let items: Vec<usize> = (0..100).collect();
+        let results = map_on_single_threads(&items, |&item| {
+            // Nested parallel iterators see a pool of one thread
+            let total: usize = (0..item).into_par_iter().sum();
+            (rayon::current_num_threads(), total)
+        })
+        .unwrap();
+        for (item, (n_threads, total)) in results.into_iter().enumerate() {
+            assert_eq!(n_threads, 1);
+            assert_eq!(total, item * item.saturating_sub(1) / 2);
+        }
+        assert!(map_on_single_threads(&[] as &[usize], |&item| item)
+            .unwrap()
+            .is_empty());
+    }
+}
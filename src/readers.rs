@@ -0,0 +1,407 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+use pyo3::{
+    exceptions::{PyIOError, PyValueError},
+    prelude::*,
+};
+use quick_xml::{events::Event, Reader};
+
+use crate::{
+    graph::{report_self_loops, WeightedDigraph},
+    print_progress, FiltrationTime, NodeIndex,
+};
+
+/// A weighted digraph read from a file, whose node `i` carries the label `labels[i]`.
+#[derive(Clone, Debug, Default)]
+pub struct LabelledDigraph {
+    pub graph: WeightedDigraph,
+    pub labels: Vec<String>,
+    /// Number of self-loops in the file, which are left out of `graph`.
+    pub skipped_self_loops: usize,
+}
+
+// Node labels and edges, as returned to Python
+type LabelsAndEdges = (Vec<String>, Vec<(NodeIndex, NodeIndex, FiltrationTime)>);
+
+/// A column of an edge list, either by position or, if the file has a header, by name.
+#[derive(Clone, Debug, PartialEq, Eq, FromPyObject)]
+pub enum ColumnRef {
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct EdgeListOptions {
+    /// Field delimiter, e.g. `b','` for CSV, or `None` to split on runs of whitespace.
+    pub delimiter: Option<u8>,
+    pub has_header: bool,
+    pub source: ColumnRef,
+    pub target: ColumnRef,
+    /// If `None`, every edge has weight 1.
+    pub weight: Option<ColumnRef>,
+}
+
+impl Default for EdgeListOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: None,
+            has_header: false,
+            source: ColumnRef::Index(0),
+            target: ColumnRef::Index(1),
+            weight: Some(ColumnRef::Index(2)),
+        }
+    }
+}
+
+// Assigns consecutive node indices to labels, in order of first appearance
+#[derive(Default)]
+struct LabelInterner {
+    indices: HashMap<String, NodeIndex>,
+    labels: Vec<String>,
+}
+
+impl LabelInterner {
+    fn intern(&mut self, label: &str) -> Result<NodeIndex, String> {
+        if let Some(&idx) = self.indices.get(label) {
+            return Ok(idx);
+        }
+        let idx = NodeIndex::try_from(self.labels.len())
+            .map_err(|_| "Too many nodes to index".to_string())?;
+        self.indices.insert(label.to_string(), idx);
+        self.labels.push(label.to_string());
+        Ok(idx)
+    }
+
+    fn finish(
+        self,
+        edges: Vec<(NodeIndex, NodeIndex, FiltrationTime)>,
+    ) -> Result<LabelledDigraph, String> {
+        let nodes = (0..self.labels.len() as NodeIndex).collect();
+        let (graph, skipped_self_loops) = WeightedDigraph::new_counting_self_loops(nodes, edges)?;
+        Ok(LabelledDigraph {
+            graph,
+            labels: self.labels,
+            skipped_self_loops,
+        })
+    }
+}
+
+/// Reads an edge list, one edge per line, skipping blank lines and lines starting with `#`.
+/// Returns the node labels, where node `i` is labelled `labels[i]`, and the edges `(i, j, weight)`.
+#[pyfunction]
+#[pyo3(name = "read_edge_list")]
+#[pyo3(signature = (path, delimiter = None, header = false, source = ColumnRef::Index(0), target = ColumnRef::Index(1), weight = ColumnRef::Index(2)))]
+pub fn read_edge_list_py(
+    path: &str,
+    delimiter: Option<char>,
+    header: bool,
+    source: ColumnRef,
+    target: ColumnRef,
+    weight: Option<ColumnRef>,
+) -> PyResult<LabelsAndEdges> {
+    let delimiter = match delimiter {
+        Some(delimiter) if delimiter.is_ascii() => Some(delimiter as u8),
+        Some(delimiter) => {
+            return Err(PyValueError::new_err(format!(
+                "Delimiter {delimiter:?} must be an ASCII character"
+            )))
+        }
+        None => None,
+    };
+    let options = EdgeListOptions {
+        delimiter,
+        has_header: header,
+        source,
+        target,
+        weight,
+    };
+    let file = File::open(path).map_err(|e| PyIOError::new_err(format!("{path}: {e}")))?;
+    let graph = read_edge_list(BufReader::new(file), &options).map_err(PyValueError::new_err)?;
+    report_self_loops(graph.skipped_self_loops, &print_progress);
+    Ok((graph.labels, graph.graph.edges))
+}
+
+/// Reads a GraphML file, taking edge weights from the attribute named `weight`
+/// (falling back to the attribute's default, then to 1).
+/// Undirected edges are added in both directions.
+/// Returns the node labels, i.e. GraphML ids, and the edges as in `read_edge_list`.
+#[pyfunction]
+#[pyo3(name = "read_graphml")]
+#[pyo3(signature = (path, weight = "weight"))]
+pub fn read_graphml_py(path: &str, weight: &str) -> PyResult<LabelsAndEdges> {
+    let file = File::open(path).map_err(|e| PyIOError::new_err(format!("{path}: {e}")))?;
+    let graph = read_graphml(BufReader::new(file), weight).map_err(PyValueError::new_err)?;
+    report_self_loops(graph.skipped_self_loops, &print_progress);
+    Ok((graph.labels, graph.graph.edges))
+}
+
+pub fn read_edge_list(
+    reader: impl BufRead,
+    options: &EdgeListOptions,
+) -> Result<LabelledDigraph, String> {
+    let mut interner = LabelInterner::default();
+    let mut edges = vec![];
+    let mut records = edge_list_records(reader, options.delimiter);
+    let header = match (options.has_header, records.next()) {
+        (true, Some(record)) => Some(record?.1),
+        (true, None) => Some(vec![]),
+        (false, first) => {
+            records = Box::new(first.into_iter().chain(records));
+            None
+        }
+    };
+    let (source, target, weight) = resolve_columns(options, header.as_deref())?;
+    for record in records {
+        let (line, fields) = record?;
+        let field = |column: usize| {
+            fields
+                .get(column)
+                .map(|field| field.as_str())
+                .ok_or_else(|| format!("Line {line} has no column {column}"))
+        };
+        let i = interner.intern(field(source)?)?;
+        let j = interner.intern(field(target)?)?;
+        let weight = match weight {
+            Some(column) => {
+                let value = field(column)?;
+                value
+                    .parse::<FiltrationTime>()
+                    .map_err(|_| format!("Line {line} has invalid weight {value}"))?
+            }
+            None => 1.0,
+        };
+        edges.push((i, j, weight));
+    }
+    interner.finish(edges)
+}
+
+// Positions of the source, target and weight columns
+fn resolve_columns(
+    options: &EdgeListOptions,
+    header: Option<&[String]>,
+) -> Result<(usize, usize, Option<usize>), String> {
+    let resolve = |column: &ColumnRef| match (column, header) {
+        (ColumnRef::Index(idx), _) => Ok(*idx),
+        (ColumnRef::Name(name), Some(header)) => header
+            .iter()
+            .position(|field| field == name)
+            .ok_or_else(|| format!("Header has no column {name}")),
+        (ColumnRef::Name(name), None) => Err(format!(
+            "Cannot select column {name} by name without a header"
+        )),
+    };
+    Ok((
+        resolve(&options.source)?,
+        resolve(&options.target)?,
+        options.weight.as_ref().map(resolve).transpose()?,
+    ))
+}
+
+type Record = Result<(usize, Vec<String>), String>;
+
+// Non-empty, non-comment lines split into fields, with their line numbers
+fn edge_list_records<'a>(
+    reader: impl BufRead + 'a,
+    delimiter: Option<u8>,
+) -> Box<dyn Iterator<Item = Record> + 'a> {
+    match delimiter {
+        None => Box::new(
+            reader
+                .lines()
+                .enumerate()
+                .map(|(idx, line)| -> Record {
+                    let line = line.map_err(|e| e.to_string())?;
+                    let fields = line.split_whitespace().map(String::from).collect();
+                    Ok((idx + 1, fields))
+                })
+                .filter(|record| {
+                    !matches!(record, Ok((_, fields)) if fields.is_empty() || fields[0].starts_with('#'))
+                }),
+        ),
+        Some(delimiter) => {
+            let csv_reader = csv::ReaderBuilder::new()
+                .delimiter(delimiter)
+                .has_headers(false)
+                .flexible(true)
+                .comment(Some(b'#'))
+                .trim(csv::Trim::All)
+                .from_reader(reader);
+            Box::new(
+                csv_reader
+                    .into_records()
+                    .map(|record| -> Record {
+                        let record = record.map_err(|e| e.to_string())?;
+                        let line = record.position().map_or(0, |position| position.line());
+                        Ok((line as usize, record.iter().map(String::from).collect()))
+                    })
+                    .filter(|record| {
+                        !matches!(record, Ok((_, fields)) if fields.iter().all(|field| field.is_empty()))
+                    }),
+            )
+        }
+    }
+}
+
+pub fn read_graphml(reader: impl BufRead, weight: &str) -> Result<LabelledDigraph, String> {
+    let mut reader = Reader::from_reader(reader);
+    reader.trim_text(true);
+    let mut interner = LabelInterner::default();
+    let mut edges = vec![];
+    let mut weight_key: Option<String> = None;
+    let mut default_weight: FiltrationTime = 1.0;
+    let mut directed_by_default = true;
+    // The edge being read, with its weight if given so far and whether it is directed
+    let mut current_edge: Option<(NodeIndex, NodeIndex, Option<FiltrationTime>, bool)> = None;
+    // Whether the text being read is the edge weight or the weight key's default
+    let mut in_weight_key = false;
+    let mut reading_weight = false;
+    let mut reading_default = false;
+    let mut buf = vec![];
+    loop {
+        let event = reader
+            .read_event_into(&mut buf)
+            .map_err(|e| format!("Invalid GraphML at {}: {e}", reader.buffer_position()))?;
+        match event {
+            Event::Start(ref element) | Event::Empty(ref element) => {
+                let is_empty = matches!(event, Event::Empty(_));
+                let attribute = |name: &str| -> Result<Option<String>, String> {
+                    element
+                        .try_get_attribute(name)
+                        .map_err(|e| e.to_string())?
+                        .map(|attr| attr.unescape_value().map(|value| value.into_owned()))
+                        .transpose()
+                        .map_err(|e| e.to_string())
+                };
+                match element.local_name().as_ref() {
+                    b"key" => {
+                        let for_edges = matches!(
+                            attribute("for")?.as_deref(),
+                            Some("edge") | Some("all") | None
+                        );
+                        if for_edges && attribute("attr.name")?.as_deref() == Some(weight) {
+                            weight_key = attribute("id")?;
+                            in_weight_key = !is_empty;
+                        }
+                    }
+                    // Empty elements have no text and no end event
+                    b"default" => {
+                        reading_default = in_weight_key && !is_empty;
+                    }
+                    b"graph" => {
+                        directed_by_default =
+                            attribute("edgedefault")?.as_deref() != Some("undirected");
+                    }
+                    b"node" => {
+                        let id = attribute("id")?.ok_or("Node without an id")?;
+                        interner.intern(&id)?;
+                    }
+                    b"edge" => {
+                        let source = attribute("source")?.ok_or("Edge without a source")?;
+                        let target = attribute("target")?.ok_or("Edge without a target")?;
+                        let directed = match attribute("directed")?.as_deref() {
+                            Some("true") => true,
+                            Some("false") => false,
+                            _ => directed_by_default,
+                        };
+                        let edge = (
+                            interner.intern(&source)?,
+                            interner.intern(&target)?,
+                            None,
+                            directed,
+                        );
+                        if is_empty {
+                            push_graphml_edge(&mut edges, edge, default_weight);
+                        } else {
+                            current_edge = Some(edge);
+                        }
+                    }
+                    b"data" => {
+                        reading_weight = !is_empty
+                            && current_edge.is_some()
+                            && weight_key.is_some()
+                            && attribute("key")? == weight_key;
+                    }
+                    _ => {}
+                }
+            }
+            Event::Text(text) if reading_weight || reading_default => {
+                let text = text.unescape().map_err(|e| e.to_string())?;
+                let value = text
+                    .trim()
+                    .parse::<FiltrationTime>()
+                    .map_err(|_| format!("Invalid weight {text}"))?;
+                if reading_weight {
+                    if let Some(edge) = current_edge.as_mut() {
+                        edge.2 = Some(value);
+                    }
+                } else {
+                    default_weight = value;
+                }
+            }
+            Event::End(element) => match element.local_name().as_ref() {
+                b"edge" => {
+                    if let Some(edge) = current_edge.take() {
+                        push_graphml_edge(&mut edges, edge, default_weight);
+                    }
+                }
+                b"key" => in_weight_key = false,
+                b"data" => reading_weight = false,
+                b"default" => reading_default = false,
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    interner.finish(edges)
+}
+
+fn push_graphml_edge(
+    edges: &mut Vec<(NodeIndex, NodeIndex, FiltrationTime)>,
+    (i, j, weight, directed): (NodeIndex, NodeIndex, Option<FiltrationTime>, bool),
+    default_weight: FiltrationTime,
+) {
+    let weight = weight.unwrap_or(default_weight);
+    edges.push((i, j, weight));
+    if !directed {
+        edges.push((j, i, weight));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRAPHML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="w" for="edge" attr.name="weight" attr.type="double"><default>2.0</default></key>
+  <graph edgedefault="directed">
+    <node id="a"/>
+    <node id="b"/>
+    <edge source="a" target="b"><data key="w"/><desc>not a weight</desc></edge>
+    <edge source="b" target="a"><data key="w">3.5</data></edge>
+    <edge source="b" target="b"><data key="w">1.0</data></edge>
+  </graph>
+</graphml>"#;
+
+    #[test]
+    fn reads_graphml() {
+        let digraph = read_graphml(GRAPHML.as_bytes(), "weight").unwrap();
+        assert_eq!(digraph.labels, vec!["a", "b"]);
+        assert_eq!(digraph.graph.edges, vec![(0, 1, 2.0), (1, 0, 3.5)]);
+    }
+
+    #[test]
+    fn reads_edge_list_with_self_loop() {
+        let text = "# source target weight\na b 1.5\nb b 2\nb c 0.5\n";
+        let digraph = read_edge_list(text.as_bytes(), &EdgeListOptions::default()).unwrap();
+        assert_eq!(digraph.labels, vec!["a", "b", "c"]);
+        assert_eq!(digraph.graph.edges, vec![(0, 1, 1.5), (1, 2, 0.5)]);
+        assert_eq!(digraph.skipped_self_loops, 1);
+    }
+}
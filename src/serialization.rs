@@ -0,0 +1,379 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    str::FromStr,
+};
+
+use lophat::utils::PersistenceDiagram;
+use pyo3::{
+    exceptions::{PyIOError, PyValueError},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    columns::{ColumnType, GrpphatiRsColumn},
+    FiltrationTime,
+};
+
+/// Formats for saving cells and diagrams.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DataFormat {
+    Json,
+    Csv,
+}
+
+impl FromStr for DataFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "json" => Ok(DataFormat::Json),
+            "csv" => Ok(DataFormat::Csv),
+            _ => Err(format!("Unknown format {name}, expected json or csv")),
+        }
+    }
+}
+
+/// A feature of a persistence diagram, with the indices of the cells that create and destroy it.
+/// Essential features have no death.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DiagramBar {
+    pub dimension: usize,
+    pub birth: FiltrationTime,
+    pub death: Option<FiltrationTime>,
+    pub birth_index: usize,
+    pub death_index: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub representative: Option<Vec<ColumnType>>,
+}
+
+// CSV has no nested fields, so the representative is written as space-separated cells
+#[derive(Serialize, Deserialize)]
+struct CsvBar {
+    dimension: usize,
+    birth: FiltrationTime,
+    death: Option<FiltrationTime>,
+    birth_index: usize,
+    death_index: Option<usize>,
+    representative: Option<String>,
+}
+
+/// Writes the cells to `path`, so they can be reloaded with `read_cells`, e.g. for `compute_rph_map`.
+/// Infinite entrance times can only be written as CSV.
+#[pyfunction]
+#[pyo3(name = "write_cells")]
+#[pyo3(signature = (path, cols, format = "json"))]
+pub fn write_cells_py(path: &str, cols: Vec<GrpphatiRsColumn>, format: &str) -> PyResult<()> {
+    let format: DataFormat = format.parse().map_err(PyValueError::new_err)?;
+    if format == DataFormat::Json {
+        check_json_times(&cols).map_err(PyValueError::new_err)?;
+    }
+    let file = File::create(path).map_err(|e| PyIOError::new_err(format!("{path}: {e}")))?;
+    write_cells(BufWriter::new(file), &cols, format).map_err(PyIOError::new_err)
+}
+
+#[pyfunction]
+#[pyo3(name = "read_cells")]
+#[pyo3(signature = (path, format = "json"))]
+pub fn read_cells_py(path: &str, format: &str) -> PyResult<Vec<GrpphatiRsColumn>> {
+    let format: DataFormat = format.parse().map_err(PyValueError::new_err)?;
+    let file = File::open(path).map_err(|e| PyIOError::new_err(format!("{path}: {e}")))?;
+    read_cells(BufReader::new(file), format).map_err(PyValueError::new_err)
+}
+
+/// Writes the diagram, which indexes into `cols`, to `path` with the birth and death time of each feature.
+/// `representatives` maps the death index of each pair, or birth index of each essential feature,
+/// to the indices of the cells in a representative.
+#[pyfunction]
+#[pyo3(name = "write_diagram")]
+#[pyo3(signature = (path, cols, diagram, representatives = None, format = "json"))]
+pub fn write_diagram_py(
+    path: &str,
+    cols: Vec<GrpphatiRsColumn>,
+    diagram: PersistenceDiagram,
+    representatives: Option<HashMap<usize, Vec<usize>>>,
+    format: &str,
+) -> PyResult<()> {
+    let format: DataFormat = format.parse().map_err(PyValueError::new_err)?;
+    let bars =
+        diagram_bars(&cols, &diagram, representatives.as_ref()).map_err(PyValueError::new_err)?;
+    let file = File::create(path).map_err(|e| PyIOError::new_err(format!("{path}: {e}")))?;
+    write_bars(BufWriter::new(file), &bars, format).map_err(PyIOError::new_err)
+}
+
+/// Reads a diagram written by `write_diagram`, returning it with any representatives,
+/// keyed as in `write_diagram`.
+#[pyfunction]
+#[pyo3(name = "read_diagram")]
+#[pyo3(signature = (path, format = "json"))]
+pub fn read_diagram_py(
+    path: &str,
+    format: &str,
+) -> PyResult<(PersistenceDiagram, HashMap<usize, Vec<GrpphatiRsColumn>>)> {
+    let format: DataFormat = format.parse().map_err(PyValueError::new_err)?;
+    let file = File::open(path).map_err(|e| PyIOError::new_err(format!("{path}: {e}")))?;
+    let bars = read_bars(BufReader::new(file), format).map_err(PyValueError::new_err)?;
+    let (diagram, representatives) = bars_to_diagram(bars);
+    let representatives = representatives
+        .into_iter()
+        .map(|(idx, rep)| {
+            let cells = rep
+                .into_iter()
+                .map(|col_type| GrpphatiRsColumn {
+                    col_type,
+                    entrance_time: None,
+                })
+                .collect();
+            (idx, cells)
+        })
+        .collect();
+    Ok((diagram, representatives))
+}
+
+/// The features of `diagram`, which indexes into `cols`, sorted by dimension, birth then death.
+/// See `write_diagram` for the keys of `representatives`.
+pub fn diagram_bars(
+    cols: &[GrpphatiRsColumn],
+    diagram: &PersistenceDiagram,
+    representatives: Option<&HashMap<usize, Vec<usize>>>,
+) -> Result<Vec<DiagramBar>, String> {
+    let cell = |idx: usize| {
+        cols.get(idx).ok_or_else(|| {
+            format!(
+                "Diagram refers to cell {idx} but there are only {}",
+                cols.len()
+            )
+        })
+    };
+    let time = |idx: usize| {
+        cell(idx)?
+            .entrance_time
+            .ok_or_else(|| format!("Cell {idx} does not have an entrance time"))
+    };
+    let representative = |key: usize| -> Result<Option<Vec<ColumnType>>, String> {
+        let Some(rep) = representatives.and_then(|reps| reps.get(&key)) else {
+            return Ok(None);
+        };
+        rep.iter()
+            .map(|&idx| cell(idx).map(|col| col.col_type))
+            .collect::<Result<_, _>>()
+            .map(Some)
+    };
+    let mut bars = vec![];
+    for &(birth, death) in diagram.paired.iter() {
+        bars.push(DiagramBar {
+            dimension: cell(birth)?.dimension(),
+            birth: time(birth)?,
+            death: Some(time(death)?),
+            birth_index: birth,
+            death_index: Some(death),
+            representative: representative(death)?,
+        });
+    }
+    for &birth in diagram.unpaired.iter() {
+        bars.push(DiagramBar {
+            dimension: cell(birth)?.dimension(),
+            birth: time(birth)?,
+            death: None,
+            birth_index: birth,
+            death_index: None,
+            representative: representative(birth)?,
+        });
+    }
+    let key = |bar: &DiagramBar| {
+        (
+            bar.dimension,
+            bar.birth,
+            bar.death.unwrap_or(FiltrationTime::INFINITY),
+            bar.birth_index,
+        )
+    };
+    bars.sort_by(|a, b| {
+        key(a)
+            .partial_cmp(&key(b))
+            .expect("Times should not be NaN")
+    });
+    Ok(bars)
+}
+
+/// Inverse of [`diagram_bars`], returning the diagram with any representatives.
+pub fn bars_to_diagram(
+    bars: Vec<DiagramBar>,
+) -> (PersistenceDiagram, HashMap<usize, Vec<ColumnType>>) {
+    let mut diagram = PersistenceDiagram::default();
+    let mut representatives = HashMap::new();
+    for bar in bars {
+        let key = match bar.death_index {
+            Some(death) => {
+                diagram.paired.insert((bar.birth_index, death));
+                death
+            }
+            None => {
+                diagram.unpaired.insert(bar.birth_index);
+                bar.birth_index
+            }
+        };
+        if let Some(rep) = bar.representative {
+            representatives.insert(key, rep);
+        }
+    }
+    (diagram, representatives)
+}
+
+/// Fails on a non-finite entrance time in JSON, which has no infinity or NaN
+/// and would otherwise write it as `null`, i.e. no entrance time.
+pub fn write_cells(
+    mut writer: impl Write,
+    cols: &[GrpphatiRsColumn],
+    format: DataFormat,
+) -> Result<(), String> {
+    match format {
+        DataFormat::Json => {
+            check_json_times(cols)?;
+            serde_json::to_writer(&mut writer, cols).map_err(|e| e.to_string())?;
+        }
+        DataFormat::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(&mut writer);
+            for col in cols {
+                csv_writer.serialize(col).map_err(|e| e.to_string())?;
+            }
+            csv_writer.flush().map_err(|e| e.to_string())?;
+        }
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+fn check_json_times(cols: &[GrpphatiRsColumn]) -> Result<(), String> {
+    let non_finite = cols
+        .iter()
+        .find(|col| col.entrance_time.is_some_and(|time| !time.is_finite()));
+    match non_finite {
+        Some(col) => Err(format!(
+            "{} enters at {}, which cannot be written as JSON",
+            col.col_type,
+            col.get_entrance_time()
+        )),
+        None => Ok(()),
+    }
+}
+
+pub fn read_cells(reader: impl Read, format: DataFormat) -> Result<Vec<GrpphatiRsColumn>, String> {
+    match format {
+        DataFormat::Json => serde_json::from_reader(reader).map_err(|e| e.to_string()),
+        DataFormat::Csv => csv::Reader::from_reader(reader)
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string()),
+    }
+}
+
+pub fn write_bars(
+    mut writer: impl Write,
+    bars: &[DiagramBar],
+    format: DataFormat,
+) -> Result<(), String> {
+    match format {
+        DataFormat::Json => {
+            serde_json::to_writer(&mut writer, bars).map_err(|e| e.to_string())?;
+        }
+        DataFormat::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(&mut writer);
+            for bar in bars {
+                let representative = bar.representative.as_ref().map(|rep| {
+                    rep.iter()
+                        .map(|cell| cell.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                });
+                let record = CsvBar {
+                    dimension: bar.dimension,
+                    birth: bar.birth,
+                    death: bar.death,
+                    birth_index: bar.birth_index,
+                    death_index: bar.death_index,
+                    representative,
+                };
+                csv_writer.serialize(record).map_err(|e| e.to_string())?;
+            }
+            csv_writer.flush().map_err(|e| e.to_string())?;
+        }
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+pub fn read_bars(reader: impl Read, format: DataFormat) -> Result<Vec<DiagramBar>, String> {
+    match format {
+        DataFormat::Json => serde_json::from_reader(reader).map_err(|e| e.to_string()),
+        DataFormat::Csv => {
+            let mut bars = vec![];
+            for record in csv::Reader::from_reader(reader).deserialize() {
+                let record: CsvBar = record.map_err(|e| e.to_string())?;
+                let representative = record
+                    .representative
+                    .map(|rep| {
+                        rep.split_whitespace()
+                            .map(ColumnType::from_str)
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                    .transpose()?;
+                bars.push(DiagramBar {
+                    dimension: record.dimension,
+                    birth: record.birth,
+                    death: record.death,
+                    birth_index: record.birth_index,
+                    death_index: record.death_index,
+                    representative,
+                });
+            }
+            Ok(bars)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(times: &[Option<FiltrationTime>]) -> Vec<GrpphatiRsColumn> {
+        times
+            .iter()
+            .enumerate()
+            .map(|(i, &entrance_time)| GrpphatiRsColumn {
+                col_type: ColumnType::Edge(i as u32, i as u32 + 1),
+                entrance_time,
+            })
+            .collect()
+    }
+
+    fn round_trip(cols: &[GrpphatiRsColumn], format: DataFormat) -> Vec<GrpphatiRsColumn> {
+        let mut bytes = vec![];
+        write_cells(&mut bytes, cols, format).unwrap();
+        read_cells(bytes.as_slice(), format).unwrap()
+    }
+
+    fn times(cols: &[GrpphatiRsColumn]) -> Vec<Option<FiltrationTime>> {
+        cols.iter().map(|col| col.entrance_time).collect()
+    }
+
+    #[test]
+    fn cells_round_trip() {
+        let cols = cells(&[Some(0.0), Some(1.5), None]);
+        for format in [DataFormat::Json, DataFormat::Csv] {
+            let read = round_trip(&cols, format);
+            assert_eq!(times(&read), times(&cols));
+            assert!(read
+                .iter()
+                .zip(cols.iter())
+                .all(|(a, b)| a.col_type == b.col_type));
+        }
+    }
+
+    #[test]
+    fn infinite_times_round_trip_as_csv_but_not_json() {
+        let cols = cells(&[Some(FiltrationTime::INFINITY)]);
+        assert_eq!(times(&round_trip(&cols, DataFormat::Csv)), times(&cols));
+        assert!(write_cells(vec![], &cols, DataFormat::Json).is_err());
+    }
+}
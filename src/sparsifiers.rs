@@ -1,11 +1,156 @@
 use rayon::prelude::*;
-use std::{collections::HashMap, sync::Mutex};
 
-use dashmap::DashMap;
-use pyo3::prelude::*;
+use pyo3::{exceptions::PyValueError, prelude::*};
 
-use crate::columns::{ColumnType, GrpphatiRsColumn};
+use crate::{
+    coefficients::{PrimeField, SignedColumn},
+    columns::{ColumnType, GrpphatiRsColumn},
+    Coefficient, NodeIndex,
+};
 
+/// Finds the index of each node and edge among a list of columns, i.e. the rows of the boundary matrix,
+/// without hashing.
+/// Nodes are given dense indices by binary search over their sorted labels, or directly when the labels are 0..n,
+/// and the edges out of each node are stored in compressed sparse row form, sorted by target.
+/// Memory is proportional to the number of 0- and 1-cells.
+pub struct FaceIndex {
+    // Sorted node labels and the column of each
+    node_labels: Vec<NodeIndex>,
+    node_cols: Vec<usize>,
+    // Edges out of dense node d are edge_targets[edge_offsets[d]..edge_offsets[d + 1]]
+    edge_offsets: Vec<usize>,
+    edge_targets: Vec<usize>,
+    edge_cols: Vec<usize>,
+}
+
+impl FaceIndex {
+    /// Indexes the 0- and 1-cells of `cols` by their position.
+    pub fn new(cols: &[GrpphatiRsColumn]) -> Self {
+        let mut nodes: Vec<(NodeIndex, usize)> = cols
+            .par_iter()
+            .enumerate()
+            .filter_map(|(idx, col)| match col.col_type {
+                ColumnType::Node(i) => Some((i, idx)),
+                _ => None,
+            })
+            .collect();
+        nodes.par_sort_unstable();
+        let (node_labels, node_cols): (Vec<_>, Vec<_>) = nodes.into_iter().unzip();
+        let mut index = Self {
+            node_labels,
+            node_cols,
+            edge_offsets: vec![],
+            edge_targets: vec![],
+            edge_cols: vec![],
+        };
+        let mut edges: Vec<(usize, usize, usize)> = cols
+            .par_iter()
+            .enumerate()
+            .filter_map(|(idx, col)| match col.col_type {
+                ColumnType::Edge(i, j) => Some((index.dense(i)?, index.dense(j)?, idx)),
+                _ => None,
+            })
+            .collect();
+        edges.par_sort_unstable();
+        let mut offsets = vec![0; index.node_labels.len() + 1];
+        for &(source, _, _) in edges.iter() {
+            offsets[source + 1] += 1;
+        }
+        for d in 0..index.node_labels.len() {
+            offsets[d + 1] += offsets[d];
+        }
+        index.edge_offsets = offsets;
+        (index.edge_targets, index.edge_cols) = edges
+            .into_iter()
+            .map(|(_, target, idx)| (target, idx))
+            .unzip();
+        index
+    }
+
+    // Position of the node among the sorted labels
+    fn dense(&self, node: NodeIndex) -> Option<usize> {
+        let n = self.node_labels.len();
+        // Labels are distinct, so if the last is n - 1 then they are exactly 0..n
+        if n > 0 && self.node_labels[n - 1] as usize == n - 1 {
+            return ((node as usize) < n).then_some(node as usize);
+        }
+        self.node_labels.binary_search(&node).ok()
+    }
+
+    /// The index of the column of a node or edge, if present.
+    pub fn get(&self, col_type: &ColumnType) -> Option<usize> {
+        match *col_type {
+            ColumnType::Node(i) => Some(self.node_cols[self.dense(i)?]),
+            ColumnType::Edge(i, j) => {
+                let (source, target) = (self.dense(i)?, self.dense(j)?);
+                let range = self.edge_offsets[source]..self.edge_offsets[source + 1];
+                let offset = self.edge_targets[range.clone()]
+                    .binary_search(&target)
+                    .ok()?;
+                Some(self.edge_cols[range.start + offset])
+            }
+            _ => None,
+        }
+    }
+
+    // Faces must come before their column, so that the boundary matrix is upper triangular
+    fn try_face(&self, col_type: &ColumnType, col_idx: usize) -> Result<usize, String> {
+        let idx = self.get(col_type).ok_or_else(|| {
+            format!("Face {col_type} of column {col_idx} is not among the columns")
+        })?;
+        if idx >= col_idx {
+            return Err(format!(
+                "Face {col_type} of column {col_idx} comes after it, at {idx}"
+            ));
+        }
+        Ok(idx)
+    }
+
+    fn face(&self, col_type: &ColumnType, col_idx: usize) -> usize {
+        self.try_face(col_type, col_idx)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// The sorted indices of the faces of the column at `col_idx`.
+    /// Panics if a face is missing or does not come before the column.
+    pub fn boundary(&self, col: &GrpphatiRsColumn, col_idx: usize) -> Vec<usize> {
+        self.try_boundary(col, col_idx)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// As [`Self::boundary`], but returns an error rather than panicking.
+    pub fn try_boundary(
+        &self,
+        col: &GrpphatiRsColumn,
+        col_idx: usize,
+    ) -> Result<Vec<usize>, String> {
+        let mut sparse_bdry = col
+            .boundary()
+            .iter()
+            .map(|row| self.try_face(&row.col_type, col_idx))
+            .collect::<Result<Vec<usize>, _>>()?;
+        sparse_bdry.sort();
+        Ok(sparse_bdry)
+    }
+
+    /// The oriented faces of the column at `col_idx`, with coefficients reduced into `field`.
+    /// Panics as [`Self::boundary`] does.
+    pub fn signed_boundary(
+        &self,
+        col: &GrpphatiRsColumn,
+        col_idx: usize,
+        field: PrimeField,
+    ) -> SignedColumn {
+        let sparse_bdry = col
+            .signed_boundary()
+            .iter()
+            .map(|(row, coeff)| (self.face(&row.col_type, col_idx), *coeff))
+            .collect();
+        field.normalise(sparse_bdry)
+    }
+}
+
+/// Sparsifies a list of columns, each of which must come after all of its faces.
 #[pyclass]
 pub struct RustListSparsifier {}
 
@@ -17,21 +162,11 @@ impl RustListSparsifier {
     }
 
     fn __call__(&mut self, cols: Vec<GrpphatiRsColumn>) -> Vec<(usize, Vec<usize>)> {
-        let mut sparse_cols = vec![];
-        let mut col2idx_map: HashMap<ColumnType, usize> = HashMap::new();
-        for col in cols {
-            let bdry = col.boundary();
-            let mut sparse_bdry = vec![];
-            for row in bdry {
-                let idx = col2idx_map.get(&row.col_type).unwrap();
-                sparse_bdry.push(*idx);
-            }
-            sparse_bdry.sort();
-            let insertion_idx = sparse_cols.len();
-            sparse_cols.push((col.dimension(), sparse_bdry));
-            col2idx_map.insert(col.col_type, insertion_idx);
-        }
-        sparse_cols
+        let index = FaceIndex::new(&cols);
+        cols.iter()
+            .enumerate()
+            .map(|(col_idx, col)| (col.dimension(), index.boundary(col, col_idx)))
+            .collect()
     }
 }
 
@@ -43,46 +178,42 @@ pub struct RustParallelListSparsifier {
 impl RustParallelListSparsifier {
     pub fn sparsify(
         &mut self,
-        cols: &Vec<GrpphatiRsColumn>,
+        cols: &[GrpphatiRsColumn],
     ) -> impl Iterator<Item = (usize, Vec<usize>)> {
-        let mut sparse_cols: Vec<Mutex<(usize, Vec<usize>)>> = Vec::with_capacity(cols.len());
-        // Build up output
-        for _ in 0..cols.len() {
-            sparse_cols.push(Mutex::new((0, vec![])));
-        }
-        let col2idx_map: DashMap<ColumnType, usize> = DashMap::new();
-        for working_dim in 0..=self.max_dim {
-            // Build boundaries
-            cols.iter()
-                .enumerate()
-                .par_bridge()
-                .filter(|(_col_idx, col)| col.dimension() == working_dim)
-                .for_each(|(col_idx, col)| {
-                    let bdry = col.boundary();
-                    let mut sparse_bdry = vec![];
-                    for row in bdry {
-                        let idx = col2idx_map.get(&row.col_type).unwrap();
-                        sparse_bdry.push(*idx);
-                    }
-                    sparse_bdry.sort();
-                    let dimension = col.dimension();
-                    *sparse_cols[col_idx].lock().unwrap() = (dimension, sparse_bdry);
-                });
-            // Insert into col2idx_map
-            if working_dim == self.max_dim {
-                continue;
-            }
-            cols.iter()
-                .enumerate()
-                .par_bridge()
-                .filter(|(_col_idx, col)| col.dimension() == working_dim)
-                .for_each(|(col_idx, col)| {
-                    col2idx_map.insert(col.col_type, col_idx);
-                })
-        }
-        sparse_cols
-            .into_iter()
-            .map(|outer| outer.into_inner().unwrap())
+        self.sparsify_with(cols, |index, col, col_idx| index.boundary(col, col_idx))
+    }
+
+    /// As [`Self::sparsify`] but keeps the orientation of each face,
+    /// with coefficients reduced into the prime field.
+    pub fn sparsify_signed(
+        &mut self,
+        cols: &[GrpphatiRsColumn],
+        field: PrimeField,
+    ) -> impl Iterator<Item = (usize, SignedColumn)> {
+        self.sparsify_with(cols, move |index, col, col_idx| {
+            index.signed_boundary(col, col_idx, field)
+        })
+    }
+
+    // Builds the boundary of each column in parallel.
+    // Columns above max_dim are left empty.
+    fn sparsify_with<T: Send>(
+        &mut self,
+        cols: &[GrpphatiRsColumn],
+        build_boundary: impl Fn(&FaceIndex, &GrpphatiRsColumn, usize) -> Vec<T> + Sync,
+    ) -> impl Iterator<Item = (usize, Vec<T>)> {
+        let index = FaceIndex::new(cols);
+        let sparse_cols: Vec<(usize, Vec<T>)> = cols
+            .par_iter()
+            .enumerate()
+            .map(|(col_idx, col)| {
+                if col.dimension() > self.max_dim {
+                    return (0, vec![]);
+                }
+                (col.dimension(), build_boundary(&index, col, col_idx))
+            })
+            .collect();
+        sparse_cols.into_iter()
     }
 }
 
@@ -97,11 +228,23 @@ impl RustParallelListSparsifier {
         println!("Sparsified");
         self.sparsify(&cols).collect()
     }
+
+    /// Sparsifies with oriented boundaries, with coefficients in Z/`prime`.
+    #[pyo3(name = "sparsify_signed")]
+    fn sparsify_signed_py(
+        &mut self,
+        cols: Vec<GrpphatiRsColumn>,
+        prime: Coefficient,
+    ) -> PyResult<Vec<(usize, SignedColumn)>> {
+        let field = PrimeField::new(prime).map_err(PyValueError::new_err)?;
+        Ok(self.sparsify_signed(&cols, field).collect())
+    }
 }
 
+/// Sparsifies one column at a time, each of which must come after all of its faces.
 #[pyclass]
 pub struct RustIteratorSparsifier {
-    col2idx_map: HashMap<ColumnType, usize>,
+    index: FaceIndex,
     current_idx: usize,
     cols: std::vec::IntoIter<GrpphatiRsColumn>,
 }
@@ -111,22 +254,85 @@ impl RustIteratorSparsifier {
     #[new]
     fn new(cols: Vec<GrpphatiRsColumn>) -> Self {
         Self {
-            col2idx_map: HashMap::new(),
+            index: FaceIndex::new(&cols),
             current_idx: 0,
             cols: cols.into_iter(),
         }
     }
     fn get_next(&mut self) -> Option<(usize, Vec<usize>)> {
         let col = self.cols.next()?;
-        let bdry = col.boundary();
-        let mut sparse_bdry = vec![];
-        for row in bdry {
-            let idx = self.col2idx_map.get(&row.col_type).unwrap();
-            sparse_bdry.push(*idx);
-        }
-        sparse_bdry.sort();
-        self.col2idx_map.insert(col.col_type, self.current_idx);
+        let sparse_bdry = self.index.boundary(&col, self.current_idx);
         self.current_idx += 1;
         Some((col.dimension(), sparse_bdry))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(col_types: &[ColumnType]) -> Vec<GrpphatiRsColumn> {
+        col_types
+            .iter()
+            .map(|&col_type| GrpphatiRsColumn {
+                col_type,
+                entrance_time: Some(0.0),
+            })
+            .collect()
+    }
+
+    // The triangle 0 → 1 → 2 with 0 → 2, on nodes labelled `labels`
+    fn triangle(labels: [NodeIndex; 3]) -> Vec<GrpphatiRsColumn> {
+        let [a, b, c] = labels;
+        cells(&[
+            ColumnType::Node(a),
+            ColumnType::Node(b),
+            ColumnType::Node(c),
+            ColumnType::Edge(a, b),
+            ColumnType::Edge(b, c),
+            ColumnType::Edge(a, c),
+            ColumnType::Triangle(a, b, c),
+        ])
+    }
+
+    #[test]
+    fn indexes_dense_and_sparse_labels() {
+        for labels in [[0, 1, 2], [7, 3, 100]] {
+            let cols = triangle(labels);
+            let index = FaceIndex::new(&cols);
+            for (idx, col) in cols.iter().enumerate().take(6) {
+                assert_eq!(index.get(&col.col_type), Some(idx));
+            }
+            assert_eq!(index.get(&ColumnType::Edge(labels[1], labels[0])), None);
+            assert_eq!(index.get(&ColumnType::Node(50)), None);
+            assert_eq!(index.boundary(&cols[6], 6), vec![3, 4, 5]);
+        }
+    }
+
+    #[test]
+    fn signed_boundary_is_reduced() {
+        let cols = triangle([0, 1, 2]);
+        let index = FaceIndex::new(&cols);
+        let field = PrimeField::new(3).unwrap();
+        let mut boundary = index.signed_boundary(&cols[6], 6, field);
+        boundary.sort();
+        assert_eq!(boundary, vec![(3, 1), (4, 1), (5, 2)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "comes after it")]
+    fn rejects_a_column_before_its_faces() {
+        let mut cols = triangle([0, 1, 2]);
+        cols.swap(5, 6);
+        let index = FaceIndex::new(&cols);
+        index.boundary(&cols[5], 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not among the columns")]
+    fn rejects_a_missing_face() {
+        let cols = triangle([0, 1, 2]);
+        let index = FaceIndex::new(&cols[..5]);
+        index.boundary(&cols[6], 6);
+    }
+}
@@ -0,0 +1,227 @@
+use std::{collections::HashMap, str::FromStr};
+
+use crate::{
+    graph::WeightedDigraph, homology::EdgeMap, pipelines::HomologyKind, FiltrationTime, NodeIndex,
+};
+
+/// The earliest time at which some node has an edge to every other node, or from every other node.
+/// From then on the filtration contains a cone, so there is no more persistent homology to see.
+/// Every node must be a key of `edge_map`; infinite if no such time exists.
+pub fn cone_time(edge_map: &EdgeMap) -> FiltrationTime {
+    cone_time_from(edge_map, 0.0, false)
+}
+
+/// The earliest time at which the filtration `edge_map` contains both a cone and every edge of `graph`.
+/// Grounding adds the edges of `graph` at time 0, so before they have all entered the filtration
+/// they may carry cycles which the cone does not fill.
+/// The directed flag complex only forms a cone once the apex has no edges in the other direction,
+/// since a double edge at the apex carries a cycle which no simplex fills.
+pub fn grounded_cone_time(
+    graph: &WeightedDigraph,
+    edge_map: &EdgeMap,
+    homology: HomologyKind,
+) -> FiltrationTime {
+    let grounded = graph
+        .edges
+        .iter()
+        .map(|(i, j, _)| {
+            edge_map
+                .get(i)
+                .and_then(|targets| targets.get(j))
+                .copied()
+                .unwrap_or(FiltrationTime::INFINITY)
+        })
+        .fold(0.0, FiltrationTime::max);
+    if grounded.is_infinite() {
+        return grounded;
+    }
+    cone_time_from(edge_map, grounded, homology == HomologyKind::Dflag)
+}
+
+// Number of edges at a node, with the times of the first and last
+#[derive(Clone, Copy)]
+struct Incidence {
+    count: usize,
+    first: FiltrationTime,
+    last: FiltrationTime,
+}
+
+impl Default for Incidence {
+    fn default() -> Self {
+        Incidence {
+            count: 0,
+            first: FiltrationTime::INFINITY,
+            last: 0.0,
+        }
+    }
+}
+
+impl Incidence {
+    fn add(&mut self, time: FiltrationTime) {
+        self.count += 1;
+        self.first = self.first.min(time);
+        self.last = self.last.max(time);
+    }
+}
+
+// The earliest time, no earlier than `earliest`, at which some node has an edge to or from every other node.
+// With `one_way`, the node must also have no edges in the other direction by then.
+fn cone_time_from(edge_map: &EdgeMap, earliest: FiltrationTime, one_way: bool) -> FiltrationTime {
+    let n_others = edge_map.len().saturating_sub(1);
+    if n_others == 0 {
+        return earliest;
+    }
+    let mut out_edges: HashMap<NodeIndex, Incidence> = HashMap::new();
+    let mut in_edges: HashMap<NodeIndex, Incidence> = HashMap::new();
+    for (&source, targets) in edge_map.iter() {
+        for (&target, &time) in targets.iter() {
+            out_edges.entry(source).or_default().add(time);
+            in_edges.entry(target).or_default().add(time);
+        }
+    }
+    let apex_time = |towards: &HashMap<NodeIndex, Incidence>,
+                     away: &HashMap<NodeIndex, Incidence>| {
+        towards
+            .iter()
+            .filter(|(_, incidence)| incidence.count == n_others)
+            .map(|(node, incidence)| (node, incidence.last.max(earliest)))
+            .filter(|(node, time)| {
+                !one_way
+                    || away
+                        .get(node)
+                        .is_none_or(|incidence| incidence.first > *time)
+            })
+            .map(|(_, time)| time)
+            .fold(FiltrationTime::INFINITY, FiltrationTime::min)
+    };
+    apex_time(&out_edges, &in_edges).min(apex_time(&in_edges, &out_edges))
+}
+
+/// Removes all edges entering after `max_time`.
+pub fn truncate(edge_map: &mut EdgeMap, max_time: FiltrationTime) {
+    for targets in edge_map.values_mut() {
+        targets.retain(|_, time| *time <= max_time);
+    }
+}
+
+/// How to truncate the filtration of each graph, or part of a graph, in the native pipelines.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TruncationStrategy {
+    /// Keep the whole filtration.
+    None,
+    /// Truncate at the [`grounded_cone_time`], after which there is no persistent homology.
+    Cone,
+}
+
+impl FromStr for TruncationStrategy {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "none" => Ok(TruncationStrategy::None),
+            "cone" => Ok(TruncationStrategy::Cone),
+            _ => Err(format!("Unknown truncation {name}, expected none or cone")),
+        }
+    }
+}
+
+/// A truncation strategy together with an optional upper bound on filtration times.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Truncation {
+    pub strategy: TruncationStrategy,
+    pub max_time: Option<FiltrationTime>,
+}
+
+impl Truncation {
+    pub fn new(strategy: &str, max_time: Option<FiltrationTime>) -> Result<Self, String> {
+        if max_time.is_some_and(|time| time.is_nan()) {
+            return Err("max_time must not be NaN".to_string());
+        }
+        Ok(Truncation {
+            strategy: strategy.parse()?,
+            max_time,
+        })
+    }
+
+    /// Truncates the filtration `edge_map`, to be grounded at `graph`, at the earlier of `max_time`
+    /// and, if the strategy is cone, its [`grounded_cone_time`] for `homology`.
+    /// Returns the time truncated at, which is infinite if nothing was removed.
+    pub fn apply(
+        &self,
+        graph: &WeightedDigraph,
+        edge_map: &mut EdgeMap,
+        homology: HomologyKind,
+    ) -> FiltrationTime {
+        let mut time = self.max_time.unwrap_or(FiltrationTime::INFINITY);
+        if let TruncationStrategy::Cone = self.strategy {
+            time = time.min(grounded_cone_time(graph, edge_map, homology));
+        }
+        if time.is_finite() {
+            truncate(edge_map, time);
+        }
+        time
+    }
+}
+
+impl Default for Truncation {
+    fn default() -> Self {
+        Truncation {
+            strategy: TruncationStrategy::None,
+            max_time: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The cycle 0 → 1 → 2 → 0 with weights 1, 1 and 5, filtered by shortest paths
+    fn cycle() -> (WeightedDigraph, EdgeMap) {
+        let graph = WeightedDigraph::new(vec![], vec![(0, 1, 1.0), (1, 2, 1.0), (2, 0, 5.0)]);
+        let edge_map = EdgeMap::from([
+            (0, HashMap::from([(1, 1.0), (2, 2.0)])),
+            (1, HashMap::from([(2, 1.0), (0, 6.0)])),
+            (2, HashMap::from([(0, 5.0), (1, 6.0)])),
+        ]);
+        (graph.unwrap(), edge_map)
+    }
+
+    // Node 0 is a cone at time 2, but the original edge 2 → 0 only enters at 5
+    #[test]
+    fn grounded_cone_waits_for_original_edges() {
+        let (graph, edge_map) = cycle();
+        assert_eq!(cone_time(&edge_map), 2.0);
+        assert_eq!(
+            grounded_cone_time(&graph, &edge_map, HomologyKind::Rph),
+            5.0
+        );
+    }
+
+    // Every node has edges in both directions before it becomes an apex
+    #[test]
+    fn dflag_cone_needs_a_one_way_apex() {
+        let (graph, edge_map) = cycle();
+        assert_eq!(
+            grounded_cone_time(&graph, &edge_map, HomologyKind::Dflag),
+            FiltrationTime::INFINITY
+        );
+    }
+
+    #[test]
+    fn applies_the_earlier_time() {
+        let (graph, mut edge_map) = cycle();
+        let truncation = Truncation::new("cone", Some(3.0)).unwrap();
+        assert_eq!(
+            truncation.apply(&graph, &mut edge_map, HomologyKind::Rph),
+            3.0
+        );
+        let n_edges: usize = edge_map.values().map(HashMap::len).sum();
+        assert_eq!(n_edges, 3);
+        let (graph, mut edge_map) = cycle();
+        let truncation = Truncation::new("none", None).unwrap();
+        let time = truncation.apply(&graph, &mut edge_map, HomologyKind::Dflag);
+        assert!(time.is_infinite());
+        assert!(Truncation::new("cone", Some(FiltrationTime::NAN)).is_err());
+    }
+}
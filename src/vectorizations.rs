@@ -0,0 +1,364 @@
+use std::cmp::Ordering;
+
+use lophat::utils::PersistenceDiagram;
+use pyo3::{exceptions::PyValueError, prelude::*};
+use rayon::prelude::*;
+
+use crate::{
+    columns::GrpphatiRsColumn,
+    invariants::{bars_in_dimension, betti_curve, to_numpy, Bar},
+    FiltrationTime,
+};
+
+/// A diagram together with the cells it indexes into, as returned by `grpph_pipeline`.
+pub type IndexedDiagram = (Vec<GrpphatiRsColumn>, PersistenceDiagram);
+
+/// Persistence landscapes λ_1, ..., λ_`num_landscapes` in `dimension`, sampled at `times`,
+/// for each of a batch of diagrams, as a NumPy array of shape (diagrams, landscapes, times).
+/// Essential bars die at `infinity`, which defaults to the largest of `times`.
+#[pyfunction]
+#[pyo3(name = "landscapes")]
+#[pyo3(signature = (diagrams, times, num_landscapes = 5, dimension = 1, infinity = None))]
+pub fn landscapes_py(
+    py: Python<'_>,
+    diagrams: Vec<IndexedDiagram>,
+    times: Vec<FiltrationTime>,
+    num_landscapes: usize,
+    dimension: usize,
+    infinity: Option<FiltrationTime>,
+) -> PyResult<PyObject> {
+    check_times(&times)?;
+    let infinity = infinity.unwrap_or_else(|| max_time(&times));
+    let batch = finite_bars_batch(&diagrams, dimension, infinity)?;
+    let result: Vec<_> = batch
+        .par_iter()
+        .map(|bars| landscapes(bars, &times, num_landscapes))
+        .collect();
+    to_numpy(py, result, "float64")
+}
+
+/// Silhouettes in `dimension`, sampled at `times`, i.e. the average of the tent function of each bar
+/// weighted by its persistence to the `power`, as a NumPy array of shape (diagrams, times).
+/// See `landscapes` for `infinity`.
+#[pyfunction]
+#[pyo3(name = "silhouettes")]
+#[pyo3(signature = (diagrams, times, power = 1.0, dimension = 1, infinity = None))]
+pub fn silhouettes_py(
+    py: Python<'_>,
+    diagrams: Vec<IndexedDiagram>,
+    times: Vec<FiltrationTime>,
+    power: f64,
+    dimension: usize,
+    infinity: Option<FiltrationTime>,
+) -> PyResult<PyObject> {
+    check_times(&times)?;
+    let infinity = infinity.unwrap_or_else(|| max_time(&times));
+    let batch = finite_bars_batch(&diagrams, dimension, infinity)?;
+    let result: Vec<_> = batch
+        .par_iter()
+        .map(|bars| silhouette(bars, &times, power))
+        .collect();
+    to_numpy(py, result, "float64")
+}
+
+/// Persistence images in `dimension` over the given birth and persistence ranges,
+/// as a NumPy array of shape (diagrams, persistence pixels, birth pixels).
+/// Each bar contributes a Gaussian with standard deviation `sigma`, evaluated at the pixel centres,
+/// weighted linearly by its persistence up to the top of the persistence range.
+/// Essential bars die at `infinity`, which defaults to the top of the birth range plus the top of the persistence range.
+#[pyfunction]
+#[pyo3(name = "persistence_images")]
+#[pyo3(signature = (
+    diagrams,
+    birth_range,
+    persistence_range,
+    resolution = (20, 20),
+    sigma = 0.1,
+    dimension = 1,
+    infinity = None,
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn persistence_images_py(
+    py: Python<'_>,
+    diagrams: Vec<IndexedDiagram>,
+    birth_range: (f64, f64),
+    persistence_range: (f64, f64),
+    resolution: (usize, usize),
+    sigma: f64,
+    dimension: usize,
+    infinity: Option<FiltrationTime>,
+) -> PyResult<PyObject> {
+    let grid = ImageGrid {
+        birth_range,
+        persistence_range,
+        resolution,
+        sigma,
+    };
+    grid.validate().map_err(PyValueError::new_err)?;
+    let infinity = infinity.unwrap_or(birth_range.1 + persistence_range.1);
+    let batch = finite_bars_batch(&diagrams, dimension, infinity)?;
+    let result: Vec<_> = batch
+        .par_iter()
+        .map(|bars| persistence_image(bars, &grid))
+        .collect();
+    to_numpy(py, result, "float64")
+}
+
+/// Betti curves in `dimension`, sampled at `times`, as a NumPy array of shape (diagrams, times).
+#[pyfunction]
+#[pyo3(name = "betti_vectors")]
+#[pyo3(signature = (diagrams, times, dimension = 1))]
+pub fn betti_vectors_py(
+    py: Python<'_>,
+    diagrams: Vec<IndexedDiagram>,
+    times: Vec<FiltrationTime>,
+    dimension: usize,
+) -> PyResult<PyObject> {
+    check_times(&times)?;
+    let batch = bars_batch(&diagrams, dimension)?;
+    to_numpy(py, betti_vectors(&batch, &times), "int64")
+}
+
+fn check_times(times: &[FiltrationTime]) -> PyResult<()> {
+    if times.iter().any(|time| time.is_nan()) {
+        return Err(PyValueError::new_err("Times must not be NaN"));
+    }
+    Ok(())
+}
+
+// Zero if there are no times, since nothing is then sampled
+fn max_time(times: &[FiltrationTime]) -> FiltrationTime {
+    times
+        .iter()
+        .copied()
+        .reduce(FiltrationTime::max)
+        .unwrap_or(0.0)
+}
+
+// Essential bars cut off at an infinite time have infinite persistence, which makes silhouettes NaN
+fn check_infinity(infinity: FiltrationTime) -> Result<(), String> {
+    if !infinity.is_finite() {
+        return Err(format!(
+            "infinity must be finite, got {infinity}; pass it explicitly if the times are not"
+        ));
+    }
+    Ok(())
+}
+
+fn bars_batch(diagrams: &[IndexedDiagram], dimension: usize) -> PyResult<Vec<Vec<Bar>>> {
+    diagrams
+        .par_iter()
+        .map(|(cols, diagram)| bars_in_dimension(cols, diagram, dimension))
+        .collect::<Result<_, _>>()
+        .map_err(PyValueError::new_err)
+}
+
+fn finite_bars_batch(
+    diagrams: &[IndexedDiagram],
+    dimension: usize,
+    infinity: FiltrationTime,
+) -> PyResult<Vec<Vec<Bar>>> {
+    check_infinity(infinity).map_err(PyValueError::new_err)?;
+    Ok(bars_batch(diagrams, dimension)?
+        .into_iter()
+        .map(|bars| finite_bars(bars, infinity))
+        .collect())
+}
+
+/// The Betti curve of each of a batch of diagrams, where essential bars are never cut off.
+pub fn betti_vectors(batch: &[Vec<Bar>], times: &[FiltrationTime]) -> Vec<Vec<usize>> {
+    batch
+        .par_iter()
+        .map(|bars| betti_curve(bars, times))
+        .collect()
+}
+
+/// Replaces infinite deaths by `infinity`, dropping bars which then have no length.
+pub fn finite_bars(bars: Vec<Bar>, infinity: FiltrationTime) -> Vec<Bar> {
+    bars.into_iter()
+        .map(|(birth, death)| (birth, death.min(infinity)))
+        .filter(|(birth, death)| birth < death)
+        .collect()
+}
+
+// The tent function of a bar, peaking halfway between birth and death
+fn tent((birth, death): Bar, t: FiltrationTime) -> f64 {
+    (t - birth).min(death - t).max(0.0)
+}
+
+/// The first `num_landscapes` persistence landscapes at each of `times`; λ_k(t) is the k-th largest tent at t.
+pub fn landscapes(bars: &[Bar], times: &[FiltrationTime], num_landscapes: usize) -> Vec<Vec<f64>> {
+    let mut result = vec![vec![0.0; times.len()]; num_landscapes];
+    let mut tents = Vec::with_capacity(bars.len());
+    for (idx, &t) in times.iter().enumerate() {
+        tents.clear();
+        tents.extend(
+            bars.iter()
+                .map(|&bar| tent(bar, t))
+                .filter(|&height| height > 0.0),
+        );
+        tents.sort_unstable_by(|a, b| b.partial_cmp(a).expect("Tents should not be NaN"));
+        for (landscape, &height) in result.iter_mut().zip(tents.iter()) {
+            landscape[idx] = height;
+        }
+    }
+    result
+}
+
+/// The silhouette at each of `times`, weighting each bar by its persistence to the `power`.
+pub fn silhouette(bars: &[Bar], times: &[FiltrationTime], power: f64) -> Vec<f64> {
+    let weights: Vec<f64> = bars
+        .iter()
+        .map(|(birth, death)| (death - birth).powf(power))
+        .collect();
+    let total: f64 = weights.iter().sum();
+    if total == 0.0 {
+        return vec![0.0; times.len()];
+    }
+    times
+        .iter()
+        .map(|&t| {
+            bars.iter()
+                .zip(weights.iter())
+                .map(|(&bar, weight)| weight * tent(bar, t))
+                .sum::<f64>()
+                / total
+        })
+        .collect()
+}
+
+/// The pixels of a persistence image.
+pub struct ImageGrid {
+    pub birth_range: (f64, f64),
+    pub persistence_range: (f64, f64),
+    /// Number of pixels along the birth and persistence axes.
+    pub resolution: (usize, usize),
+    pub sigma: f64,
+}
+
+impl ImageGrid {
+    pub fn validate(&self) -> Result<(), String> {
+        let (b0, b1) = self.birth_range;
+        let (p0, p1) = self.persistence_range;
+        // Written so that NaN bounds are rejected
+        if b0.partial_cmp(&b1) != Some(Ordering::Less)
+            || p0.partial_cmp(&p1) != Some(Ordering::Less)
+        {
+            return Err("Ranges must be non-empty, with the lower bound first".to_string());
+        }
+        if p1 <= 0.0 {
+            return Err("The persistence range must include positive persistence".to_string());
+        }
+        if self.resolution.0 == 0 || self.resolution.1 == 0 {
+            return Err("Resolution must be positive".to_string());
+        }
+        if self.sigma.partial_cmp(&0.0) != Some(Ordering::Greater) {
+            return Err("sigma must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    // Centre of pixel idx along an axis with the given range and number of pixels
+    fn centre(range: (f64, f64), pixels: usize, idx: usize) -> f64 {
+        range.0 + (range.1 - range.0) * (idx as f64 + 0.5) / pixels as f64
+    }
+}
+
+/// The persistence image of the bars, indexed by persistence pixel then birth pixel.
+pub fn persistence_image(bars: &[Bar], grid: &ImageGrid) -> Vec<Vec<f64>> {
+    let (birth_pixels, persistence_pixels) = grid.resolution;
+    let mut image = vec![vec![0.0; birth_pixels]; persistence_pixels];
+    let variance = grid.sigma * grid.sigma;
+    let normalisation = 1.0 / (2.0 * std::f64::consts::PI * variance);
+    for &(birth, death) in bars {
+        let persistence = death - birth;
+        let weight = (persistence / grid.persistence_range.1).min(1.0);
+        for (p_idx, row) in image.iter_mut().enumerate() {
+            let p = ImageGrid::centre(grid.persistence_range, persistence_pixels, p_idx);
+            let dp = (p - persistence) * (p - persistence);
+            for (b_idx, pixel) in row.iter_mut().enumerate() {
+                let b = ImageGrid::centre(grid.birth_range, birth_pixels, b_idx);
+                let db = (b - birth) * (b - birth);
+                *pixel += weight * normalisation * (-(db + dp) / (2.0 * variance)).exp();
+            }
+        }
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cuts_essential_bars_at_a_finite_infinity() {
+        let bars = vec![
+            (0.0, 2.0),
+            (1.0, FiltrationTime::INFINITY),
+            (3.0, FiltrationTime::INFINITY),
+        ];
+        let bars = finite_bars(bars, 3.0);
+        assert_eq!(bars, vec![(0.0, 2.0), (1.0, 3.0)]);
+        let values = silhouette(&bars, &[0.0, 1.0, 2.0, 3.0], 1.0);
+        assert_eq!(values, vec![0.0, 1.0 / 2.0, 1.0 / 2.0, 0.0]);
+    }
+
+    #[test]
+    fn rejects_non_finite_infinity() {
+        assert!(check_infinity(3.0).is_ok());
+        assert!(check_infinity(FiltrationTime::INFINITY).is_err());
+        assert!(check_infinity(FiltrationTime::NEG_INFINITY).is_err());
+        assert!(check_infinity(FiltrationTime::NAN).is_err());
+        assert_eq!(max_time(&[1.0, 4.0, 2.0]), 4.0);
+        assert_eq!(max_time(&[]), 0.0);
+        assert!(check_infinity(max_time(&[1.0, FiltrationTime::INFINITY])).is_err());
+    }
+
+    #[test]
+    fn landscapes_order_the_tents() {
+        let times = [0.0, 1.0, 1.5, 2.0, 3.0, 4.0, 5.0];
+        // A single bar peaks at its midpoint, with half its length
+        let single = landscapes(&[(0.0, 4.0)], &times, 2);
+        assert_eq!(single[0], vec![0.0, 1.0, 1.5, 2.0, 1.0, 0.0, 0.0]);
+        assert_eq!(single[1], vec![0.0; times.len()]);
+        let nested = landscapes(&[(1.0, 3.0), (0.0, 4.0)], &times, 3);
+        assert_eq!(nested[0], single[0]);
+        assert_eq!(nested[1], vec![0.0, 0.0, 0.5, 1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(nested[2], vec![0.0; times.len()]);
+    }
+
+    // Pixel centres are at birth 0.5, 1.5, 2.5, 3.5 and persistence 1, 3
+    #[test]
+    fn persistence_image_peaks_at_the_nearest_pixel() {
+        let grid = ImageGrid {
+            birth_range: (0.0, 4.0),
+            persistence_range: (0.0, 4.0),
+            resolution: (4, 2),
+            sigma: 1.0,
+        };
+        grid.validate().unwrap();
+        let image = persistence_image(&[(1.5, 4.5)], &grid);
+        assert_eq!(image.len(), 2);
+        assert!(image.iter().all(|row| row.len() == 4));
+        // Weighted by its persistence over the top of the range
+        let peak = 0.75 / (2.0 * std::f64::consts::PI);
+        assert!((image[1][1] - peak).abs() < 1e-12);
+        let pixels = image.iter().flatten();
+        assert!(pixels.filter(|&&pixel| pixel >= image[1][1]).count() == 1);
+        // Persistence beyond the range is weighted as its top
+        let image = persistence_image(&[(0.5, 9.5)], &grid);
+        assert!(image[1][0] > image[0][0]);
+        let far = (-(9.0_f64 - 3.0).powi(2) / 2.0).exp() / (2.0 * std::f64::consts::PI);
+        assert!((image[1][0] - far).abs() < 1e-12);
+        assert!(ImageGrid { sigma: 0.0, ..grid }.validate().is_err());
+    }
+
+    #[test]
+    fn betti_vectors_keep_essential_bars() {
+        let batch = vec![vec![(0.0, 2.0), (1.0, FiltrationTime::INFINITY)], vec![]];
+        assert_eq!(
+            betti_vectors(&batch, &[0.0, 1.0, 2.0, 100.0]),
+            vec![vec![1, 2, 1, 1], vec![0, 0, 0, 0]]
+        );
+    }
+}